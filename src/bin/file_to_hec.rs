@@ -1,13 +1,21 @@
 use std::io::Read;
+use std::path::PathBuf;
 
 /// Pipe stdin to HTTP Event Collector!
 use clap::*;
 use serde_json::json;
 use splunk::errors::SplunkError;
 use splunk::hec::HecClient;
+use splunk::{ServerConfig, ServerConfigType};
 
 #[derive(Parser)]
 struct Cli {
+    /// Config file with connection profiles, defaults to ~/.splunkrc
+    #[arg(long, env = "SPLUNKRC")]
+    config: Option<PathBuf>,
+    /// Profile to load from the config file
+    #[arg(long, env = "SPLUNK_PROFILE")]
+    profile: Option<String>,
     #[arg(short, long, env = "SPLUNK_INDEX")]
     index: Option<String>,
     #[arg(short = 'n', long, env = "SPLUNK_HOSTNAME")]
@@ -35,36 +43,41 @@ struct Cli {
 async fn main() -> Result<(), SplunkError> {
     let cli = Cli::parse();
 
-    // in case they're using environment variables
-    let mut serverconfig = splunk::ServerConfig::default().with_verify_tls(!cli.no_verify_tls);
-    serverconfig = match cli.token {
-        Some(token) => serverconfig.with_token(token),
-        None => serverconfig,
-    };
-
-    serverconfig = match cli.port {
-        Some(port) => serverconfig.with_port(port),
-        None => serverconfig.with_port(8088),
-    };
-
-    serverconfig = match cli.hostname {
-        Some(hostname) => serverconfig.with_hostname(hostname),
-        None => serverconfig,
+    let mut serverconfig = match cli.config.is_some() || cli.profile.is_some() {
+        true => ServerConfig::from_splunkrc(
+            cli.config.as_deref(),
+            cli.profile.as_deref(),
+            ServerConfigType::Hec,
+        )?,
+        false => ServerConfig::default().with_port(8088),
     };
+    // the command line wins over the profile
+    if cli.no_verify_tls {
+        serverconfig = serverconfig.with_verify_tls(false);
+    }
+    if let Some(token) = cli.token {
+        serverconfig = serverconfig.with_token(token);
+    }
+    if let Some(port) = cli.port {
+        serverconfig = serverconfig.with_port(port);
+    }
+    if let Some(hostname) = cli.hostname {
+        serverconfig = serverconfig.with_hostname(hostname);
+    }
 
     // set up the HecClient
     let mut hec = HecClient::with_serverconfig(serverconfig);
 
     if let Some(index) = cli.index {
-        hec = hec.with_index(index);
+        hec = hec.with_index(&index);
     }
     if let Some(val) = cli.source {
-        hec = hec.with_source(val)
+        hec = hec.with_source(&val)
     } else {
         hec = hec.with_source(&cli.filename);
     };
     if let Some(val) = cli.sourcetype {
-        hec = hec.with_sourcetype(val)
+        hec = hec.with_sourcetype(&val)
     };
 
     eprintln!("{:?}", hec);
@@ -77,9 +90,9 @@ async fn main() -> Result<(), SplunkError> {
         .map_err(|err| SplunkError::Generic(err.to_string()))?;
 
     let data = json!(buffer.trim());
-    hec.enqueue(data).await;
+    hec.enqueue(data).await?;
     match hec.flush(None).await {
-        Ok(val) => eprintln!("Sent {} events!", val),
+        Ok(report) => eprintln!("Sent {} events!", report.sent),
         Err(err) => eprintln!("Failure sending event: {err:?}"),
     }
     Ok(())
@@ -1,14 +1,23 @@
 use std::io;
+use std::path::PathBuf;
 
 /// Pipe stdin to HTTP Event Collector!
 use clap::*;
 use serde_json::json;
 use splunk::errors::SplunkError;
-use splunk::hec::HecClient;
+use splunk::hec::{HecClient, HecSender, HecSenderConfig};
+use splunk::retry::RetryPolicy;
+use splunk::{ServerConfig, ServerConfigType};
 
 #[derive(Parser)]
 #[command(version, about)]
 struct Cli {
+    /// Config file with connection profiles, defaults to ~/.splunkrc
+    #[arg(long, env = "SPLUNKRC")]
+    config: Option<PathBuf>,
+    /// Profile to load from the config file
+    #[arg(long, env = "SPLUNK_PROFILE")]
+    profile: Option<String>,
     #[arg(short, long, env = "SPLUNK_INDEX")]
     index: Option<String>,
     #[arg(short = 'n', long, env = "SPLUNK_HOSTNAME")]
@@ -21,6 +30,9 @@ struct Cli {
     source: Option<String>,
     #[arg(short = 'S', long, env = "SPLUNK_SOURCETYPE")]
     sourcetype: Option<String>,
+    /// Send lines to the raw endpoint, so the sourcetype's line breaking and timestamping rules apply
+    #[arg(short, long, action = clap::ArgAction::SetTrue, env = "SPLUNK_RAW")]
+    raw: bool,
     /// Enable debug mode
     #[arg(short, long, action = clap::ArgAction::SetTrue, env)]
     debug: Option<bool>,
@@ -30,11 +42,19 @@ struct Cli {
 async fn main() -> Result<(), SplunkError> {
     let cli = Cli::parse();
 
-    // in case they're using environment variables
-    let serverconfig = splunk::ServerConfig::try_from_env(splunk::ServerConfigType::Hec)?;
+    // from a profile if one's asked for, otherwise the environment variables
+    let serverconfig = match cli.config.is_some() || cli.profile.is_some() {
+        true => ServerConfig::from_splunkrc(
+            cli.config.as_deref(),
+            cli.profile.as_deref(),
+            ServerConfigType::Hec,
+        )?,
+        false => ServerConfig::try_from_env(ServerConfigType::Hec)?,
+    };
 
-    // set up the HecClient
-    let mut hec = HecClient::with_serverconfig(serverconfig);
+    // set up the HecClient, retrying busy servers and connection problems
+    let mut hec =
+        HecClient::with_serverconfig(serverconfig).with_retry_policy(RetryPolicy::default());
 
     if let Some(port) = cli.port {
         hec.serverconfig = hec.serverconfig.with_port(port);
@@ -43,48 +63,93 @@ async fn main() -> Result<(), SplunkError> {
         hec.serverconfig = hec.serverconfig.with_hostname(hostname);
     }
     if let Some(index) = cli.index {
-        hec = hec.with_index(index);
+        hec = hec.with_index(&index);
     }
     if let Some(val) = cli.source {
-        hec = hec.with_source(val)
+        hec = hec.with_source(&val)
     };
     if let Some(val) = cli.sourcetype {
-        hec = hec.with_sourcetype(val)
+        hec = hec.with_sourcetype(&val)
     };
+    if cli.raw {
+        // the raw endpoint needs a channel
+        hec = hec.with_random_channel();
+    }
 
     if cli.debug.unwrap_or_default() {
         eprintln!("config: {hec:?}");
         eprintln!("Waiting for input...");
     }
 
+    // raw lines are sent straight from the client, everything else goes through the background sender
+    let (raw_client, sender) = match cli.raw {
+        true => (Some(hec), None),
+        false => (
+            None,
+            Some(HecSender::spawn(
+                hec,
+                HecSenderConfig::default().with_batch_size(10),
+            )),
+        ),
+    };
+
     let mut buffer = String::new();
+    let mut raw_lines: Vec<String> = Vec::new();
     let stdin = io::stdin(); // We get `Stdin` here.
     while stdin
         .read_line(&mut buffer)
         .map_err(|err| SplunkError::Generic(err.to_string()))?
         > 0
     {
-        if buffer.trim().len() != 0 {
-            let data = json!(buffer.trim());
-
-            if cli.debug.unwrap_or_default() {
-                eprintln!("Sending {data:?}");
+        if let Some(hec) = &raw_client {
+            let line = buffer.trim_end_matches(['\r', '\n']);
+            if !line.trim().is_empty() {
+                raw_lines.push(line.to_string());
             }
-            hec.enqueue(data).await;
-        }
-        if hec.queue_size().await >= 10 {
-            match hec.flush(None).await {
-                Ok(val) => eprintln!("Sent {} events!", val),
-                Err(err) => eprintln!("Failure sending event: {err:?}"),
+            if raw_lines.len() >= 10 {
+                send_raw(hec, &mut raw_lines).await?;
+            }
+        } else if let Some(sender) = &sender {
+            if !buffer.trim().is_empty() {
+                let data = json!(buffer.trim());
+
+                if cli.debug.unwrap_or_default() {
+                    eprintln!("Sending {data:?}");
+                }
+                sender.send(data).await?;
             }
         }
 
         buffer.clear();
     }
-    match hec.flush(None).await {
-        Ok(val) => eprintln!("Sent {} events!", val),
-        Err(err) => eprintln!("Failure sending event: {err:?}"),
+    if let Some(hec) = &raw_client {
+        if !raw_lines.is_empty() {
+            send_raw(hec, &mut raw_lines).await?;
+        }
+    }
+    if let Some(sender) = sender {
+        let stats = sender.shutdown().await?;
+        eprintln!("Sent {} events!", stats.sent);
+        if stats.failed > 0 {
+            eprintln!("Failed to send {} events", stats.failed);
+        }
     }
 
     Ok(())
 }
+
+/// Send the buffered lines to the raw endpoint and clear the buffer.
+///
+/// The client's already retried by the time this fails, so give up rather than dropping the lines and carrying on.
+async fn send_raw(hec: &HecClient, raw_lines: &mut Vec<String>) -> Result<(), SplunkError> {
+    if let Err(err) = hec.send_raw_lines(raw_lines).await {
+        if let SplunkError::HecPartiallySent { sent, .. } = &err {
+            raw_lines.drain(..*sent);
+        }
+        eprintln!("Failed to send {} lines: {err:?}", raw_lines.len());
+        return Err(err);
+    }
+    eprintln!("Sent {} lines!", raw_lines.len());
+    raw_lines.clear();
+    Ok(())
+}
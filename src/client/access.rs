@@ -0,0 +1,470 @@
+//! Managing users and roles, through `authentication/users` and `authorization/roles`
+//!
+//! [Role] and [User] use the same field names as `authorize.conf` and the REST API, so they can be read from (and
+//! written to) YAML or JSON files and compared with what's on the server. The server fills in the settings a file
+//! leaves out, like the quotas and default app, so use [Role::differences_from] rather than `==` to see whether a
+//! role needs updating.
+//!
+//! ```no_run
+//! use splunk::client::{Role, SplunkClient};
+//! # async fn example(client: SplunkClient, wanted: Vec<Role>) -> Result<(), splunk::errors::SplunkError> {
+//! for role in wanted {
+//!     match client.get_role(&role.name).await? {
+//!         Some(existing) if role.differences_from(&existing).is_empty() => {}
+//!         Some(_) => client.update_role(&role).await?,
+//!         None => client.create_role(&role).await?,
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! <https://docs.splunk.com/Documentation/Splunk/latest/RESTREF/RESTaccess>
+
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use super::{non_empty, splunk_bool, splunk_int, Entries, Entry, SplunkClient};
+use crate::errors::SplunkError;
+use crate::secret::Secret;
+
+const USERS_ENDPOINT: &str = "/services/authentication/users";
+const ROLES_ENDPOINT: &str = "/services/authorization/roles";
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+/// A role, from `authorization/roles`
+pub struct Role {
+    /// The role's name
+    #[serde(default)]
+    pub name: String,
+    /// Capabilities the role has itself, not counting the ones from [Role::imported_roles]
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// Roles this one inherits capabilities and index access from
+    #[serde(default)]
+    pub imported_roles: Vec<String>,
+    /// Indexes the role can search, which can use wildcards
+    #[serde(rename = "srchIndexesAllowed", default)]
+    pub srch_indexes_allowed: Vec<String>,
+    /// Indexes searched when a search doesn't say which
+    #[serde(rename = "srchIndexesDefault", default)]
+    pub srch_indexes_default: Vec<String>,
+    /// How many searches a user with this role can run at once
+    #[serde(
+        rename = "srchJobsQuota",
+        default,
+        deserialize_with = "splunk_int",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub srch_jobs_quota: Option<i64>,
+    /// How many real-time searches a user with this role can run at once
+    #[serde(
+        rename = "rtSrchJobsQuota",
+        default,
+        deserialize_with = "splunk_int",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub rt_srch_jobs_quota: Option<i64>,
+    /// How much disk a user with this role's search results can use, in MB
+    #[serde(
+        rename = "srchDiskQuota",
+        default,
+        deserialize_with = "splunk_int",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub srch_disk_quota: Option<i64>,
+    /// How many searches all the users with this role can run at once, 0 for no limit
+    #[serde(
+        rename = "cumulativeSrchJobsQuota",
+        default,
+        deserialize_with = "splunk_int",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub cumulative_srch_jobs_quota: Option<i64>,
+    /// How far back a search can go, in seconds, -1 for no limit
+    #[serde(
+        rename = "srchTimeWin",
+        default,
+        deserialize_with = "splunk_int",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub srch_time_win: Option<i64>,
+    /// A search added to every search a user with this role runs
+    #[serde(
+        rename = "srchFilter",
+        default,
+        deserialize_with = "non_empty",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub srch_filter: Option<String>,
+    /// The app users with this role start in
+    #[serde(
+        rename = "defaultApp",
+        default,
+        deserialize_with = "non_empty",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub default_app: Option<String>,
+}
+
+impl Role {
+    /// A role with no capabilities or settings
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Sort the lists and drop duplicates, like roles from the server
+    pub fn normalized(mut self) -> Self {
+        for list in [
+            &mut self.capabilities,
+            &mut self.imported_roles,
+            &mut self.srch_indexes_allowed,
+            &mut self.srch_indexes_default,
+        ] {
+            list.sort();
+            list.dedup();
+        }
+        self
+    }
+
+    /// The settings [SplunkClient::update_role] would change on `current`, by their `authorize.conf` names.
+    ///
+    /// Lists are compared ignoring their order, and settings which are `None` here are skipped, since updating
+    /// leaves them alone.
+    pub fn differences_from(&self, current: &Role) -> Vec<&'static str> {
+        let wanted = self.clone().normalized();
+        let current = current.clone().normalized();
+        let mut differences = Vec::new();
+        for (key, wanted, current) in [
+            ("capabilities", &wanted.capabilities, &current.capabilities),
+            (
+                "imported_roles",
+                &wanted.imported_roles,
+                &current.imported_roles,
+            ),
+            (
+                "srchIndexesAllowed",
+                &wanted.srch_indexes_allowed,
+                &current.srch_indexes_allowed,
+            ),
+            (
+                "srchIndexesDefault",
+                &wanted.srch_indexes_default,
+                &current.srch_indexes_default,
+            ),
+        ] {
+            if wanted != current {
+                differences.push(key);
+            }
+        }
+        for (key, wanted, current) in [
+            (
+                "srchJobsQuota",
+                wanted.srch_jobs_quota,
+                current.srch_jobs_quota,
+            ),
+            (
+                "rtSrchJobsQuota",
+                wanted.rt_srch_jobs_quota,
+                current.rt_srch_jobs_quota,
+            ),
+            (
+                "srchDiskQuota",
+                wanted.srch_disk_quota,
+                current.srch_disk_quota,
+            ),
+            (
+                "cumulativeSrchJobsQuota",
+                wanted.cumulative_srch_jobs_quota,
+                current.cumulative_srch_jobs_quota,
+            ),
+            ("srchTimeWin", wanted.srch_time_win, current.srch_time_win),
+        ] {
+            if wanted.is_some() && wanted != current {
+                differences.push(key);
+            }
+        }
+        for (key, wanted, current) in [
+            ("srchFilter", &wanted.srch_filter, &current.srch_filter),
+            ("defaultApp", &wanted.default_app, &current.default_app),
+        ] {
+            if wanted.is_some() && wanted != current {
+                differences.push(key);
+            }
+        }
+        differences
+    }
+
+    /// The form to create or update it with - lists are sent in full, so anything that's not in them is removed
+    fn form(&self) -> Vec<(&'static str, String)> {
+        let mut form = Vec::new();
+        push_list(&mut form, "capabilities", &self.capabilities);
+        push_list(&mut form, "imported_roles", &self.imported_roles);
+        push_list(&mut form, "srchIndexesAllowed", &self.srch_indexes_allowed);
+        push_list(&mut form, "srchIndexesDefault", &self.srch_indexes_default);
+        for (key, value) in [
+            ("srchJobsQuota", self.srch_jobs_quota),
+            ("rtSrchJobsQuota", self.rt_srch_jobs_quota),
+            ("srchDiskQuota", self.srch_disk_quota),
+            ("cumulativeSrchJobsQuota", self.cumulative_srch_jobs_quota),
+            ("srchTimeWin", self.srch_time_win),
+        ] {
+            if let Some(value) = value {
+                form.push((key, value.to_string()));
+            }
+        }
+        if let Some(srch_filter) = &self.srch_filter {
+            form.push(("srchFilter", srch_filter.to_owned()));
+        }
+        if let Some(default_app) = &self.default_app {
+            form.push(("defaultApp", default_app.to_owned()));
+        }
+        form
+    }
+}
+
+impl From<Entry<Role>> for Role {
+    fn from(entry: Entry<Role>) -> Self {
+        Role {
+            name: entry.name,
+            ..entry.content
+        }
+        .normalized()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+/// A user, from `authentication/users`
+pub struct User {
+    /// Their username
+    #[serde(default)]
+    pub name: String,
+    /// Their full name
+    #[serde(
+        default,
+        deserialize_with = "non_empty",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub realname: Option<String>,
+    /// Their email address
+    #[serde(
+        default,
+        deserialize_with = "non_empty",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub email: Option<String>,
+    /// The roles they have
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// The app they start in
+    #[serde(
+        rename = "defaultApp",
+        default,
+        deserialize_with = "non_empty",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub default_app: Option<String>,
+    /// Their time zone
+    #[serde(
+        default,
+        deserialize_with = "non_empty",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub tz: Option<String>,
+    /// Where the user's from - `Splunk`, `LDAP`, `SAML` and so on. This is set by the server.
+    #[serde(rename = "type", default, skip_serializing)]
+    pub user_type: String,
+    /// Have they been locked out after too many failed logins? This is set by the server.
+    #[serde(
+        rename = "locked-out",
+        default,
+        deserialize_with = "splunk_bool",
+        skip_serializing
+    )]
+    pub locked_out: bool,
+}
+
+impl User {
+    /// A user with these roles
+    pub fn new(name: &str, roles: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            roles: roles.iter().map(|role| role.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// The form to create or update it with - settings which are `None` aren't sent, so they're left alone
+    fn form(&self) -> Vec<(&'static str, String)> {
+        let mut form = Vec::new();
+        for (key, value) in [("realname", &self.realname), ("email", &self.email)] {
+            if let Some(value) = value {
+                form.push((key, value.to_owned()));
+            }
+        }
+        push_list(&mut form, "roles", &self.roles);
+        if let Some(default_app) = &self.default_app {
+            form.push(("defaultApp", default_app.to_owned()));
+        }
+        if let Some(tz) = &self.tz {
+            form.push(("tz", tz.to_owned()));
+        }
+        form
+    }
+}
+
+impl From<Entry<User>> for User {
+    fn from(entry: Entry<User>) -> Self {
+        let mut user = User {
+            name: entry.name,
+            ..entry.content
+        };
+        user.roles.sort();
+        user.roles.dedup();
+        user
+    }
+}
+
+/// Lists are sent as the same key repeated, and an empty value clears them
+fn push_list(form: &mut Vec<(&'static str, String)>, key: &'static str, values: &[String]) {
+    if values.is_empty() {
+        form.push((key, String::new()));
+    }
+    form.extend(values.iter().map(|value| (key, value.to_owned())));
+}
+
+/// `<endpoint>/<name>`
+fn item_endpoint(endpoint: &str, name: &str) -> String {
+    format!("{endpoint}/{}", urlencoding::encode(name))
+}
+
+impl SplunkClient {
+    /// List all the roles
+    pub async fn list_roles(&self) -> Result<Vec<Role>, SplunkError> {
+        let response: Entries<Role> = self
+            .request(Method::GET, ROLES_ENDPOINT)
+            .query("count", "0")
+            .send_json()
+            .await?;
+        Ok(response.entry.into_iter().map(Role::from).collect())
+    }
+
+    /// Get a role, or `None` if it doesn't exist
+    pub async fn get_role(&self, name: &str) -> Result<Option<Role>, SplunkError> {
+        let endpoint = item_endpoint(ROLES_ENDPOINT, name);
+        match self
+            .request(Method::GET, &endpoint)
+            .send_json::<Entries<Role>>()
+            .await
+        {
+            Ok(response) => Ok(Some(response.first(&endpoint)?.into())),
+            Err(SplunkError::ApiError { status: 404, .. }) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Create a role
+    pub async fn create_role(&self, role: &Role) -> Result<(), SplunkError> {
+        let mut form = vec![("name", role.name.to_owned())];
+        form.extend(role.form());
+        self.request(Method::POST, ROLES_ENDPOINT)
+            .form(form)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// Update a role to match this one. Lists are replaced, and settings which are `None` are left alone.
+    pub async fn update_role(&self, role: &Role) -> Result<(), SplunkError> {
+        self.request(Method::POST, &item_endpoint(ROLES_ENDPOINT, &role.name))
+            .form(role.form())
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// Delete a role
+    pub async fn delete_role(&self, name: &str) -> Result<(), SplunkError> {
+        self.request(Method::DELETE, &item_endpoint(ROLES_ENDPOINT, name))
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// List all the users
+    pub async fn list_users(&self) -> Result<Vec<User>, SplunkError> {
+        let response: Entries<User> = self
+            .request(Method::GET, USERS_ENDPOINT)
+            .query("count", "0")
+            .send_json()
+            .await?;
+        Ok(response.entry.into_iter().map(User::from).collect())
+    }
+
+    /// Get a user, or `None` if they don't exist
+    pub async fn get_user(&self, name: &str) -> Result<Option<User>, SplunkError> {
+        let endpoint = item_endpoint(USERS_ENDPOINT, name);
+        match self
+            .request(Method::GET, &endpoint)
+            .send_json::<Entries<User>>()
+            .await
+        {
+            Ok(response) => Ok(Some(response.first(&endpoint)?.into())),
+            Err(SplunkError::ApiError { status: 404, .. }) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Create a user, who needs at least one role
+    pub async fn create_user(&self, user: &User, password: &Secret) -> Result<(), SplunkError> {
+        let mut form = vec![
+            ("name", user.name.to_owned()),
+            ("password", password.expose().to_owned()),
+        ];
+        form.extend(user.form());
+        self.request(Method::POST, USERS_ENDPOINT)
+            .form(form)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// Update a user's roles to match this one, along with their name, email, default app and time zone if they're
+    /// set. Use an empty string to clear one of those.
+    pub async fn update_user(&self, user: &User) -> Result<(), SplunkError> {
+        self.request(Method::POST, &item_endpoint(USERS_ENDPOINT, &user.name))
+            .form(user.form())
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// Change a user's password. Changing your own needs the old one.
+    pub async fn set_user_password(
+        &self,
+        name: &str,
+        password: &Secret,
+        old_password: Option<&Secret>,
+    ) -> Result<(), SplunkError> {
+        let mut form = vec![("password", password.expose().to_owned())];
+        if let Some(old_password) = old_password {
+            form.push(("oldpassword", old_password.expose().to_owned()));
+        }
+        self.request(Method::POST, &item_endpoint(USERS_ENDPOINT, name))
+            .form(form)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// Delete a user
+    pub async fn delete_user(&self, name: &str) -> Result<(), SplunkError> {
+        self.request(Method::DELETE, &item_endpoint(USERS_ENDPOINT, name))
+            .send()
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+//! Logging in to stacks which use SSO or multi-factor authentication
+//!
+//! There are a few ways to get a session when a username and password aren't enough:
+//!
+//! - if there's an MFA provider like Duo or RSA in front of `auth/login`, set a [PasscodeHook] with
+//!   [SplunkClient::with_passcode_hook] and it's asked for the passcode on each [SplunkClient::login]
+//! - with SAML or ProxySSO, log in with a browser and hand the session key, `splunkd_<port>` cookie or auth token
+//!   to [SplunkClient::login_with_session]
+//! - once you've got a session, [SplunkClient::create_auth_token] makes a long-lived token to use with
+//!   [crate::ServerConfig::with_token], so you don't have to go through that again
+//!
+//! ```no_run
+//! use splunk::client::{AuthenticatedSessionMode, SplunkClient};
+//! use splunk::secret::Secret;
+//! # async fn example(mut client: SplunkClient) -> Result<(), splunk::errors::SplunkError> {
+//! let session_key = Secret::from_command("pbpaste", &[])?;
+//! client
+//!     .login_with_session(AuthenticatedSessionMode::Token(session_key))
+//!     .await?;
+//! let token = client.create_auth_token("cli", Some("+30d")).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use log::debug;
+
+use super::{AuthenticatedSessionMode, NewToken, SplunkClient};
+use crate::errors::SplunkError;
+use crate::secret::Secret;
+
+type PasscodeFn = dyn Fn(&str) -> Result<Secret, SplunkError> + Send + Sync;
+
+#[derive(Clone)]
+/// Asks for a multi-factor passcode (from Duo, RSA and so on) when logging in, given the username
+pub struct PasscodeHook(Arc<PasscodeFn>);
+
+impl PasscodeHook {
+    /// Wrap a function which returns the passcode, like a prompt on the terminal
+    pub fn new(hook: impl Fn(&str) -> Result<Secret, SplunkError> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(hook))
+    }
+
+    /// Ask for the passcode
+    pub(crate) fn passcode(&self, username: &str) -> Result<Secret, SplunkError> {
+        (self.0)(username)
+    }
+}
+
+impl Debug for PasscodeHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PasscodeHook")
+    }
+}
+
+impl SplunkClient {
+    /// Ask for an MFA passcode when logging in with a username and password, see the [module documentation](self)
+    pub fn with_passcode_hook(self, hook: PasscodeHook) -> Self {
+        Self {
+            passcode_hook: Some(hook),
+            ..self
+        }
+    }
+
+    /// Use a session that was set up somewhere else, like a SAML or ProxySSO login in a browser.
+    ///
+    /// The session's checked against `authentication/current-context`, and if it's rejected the client's left
+    /// without one.
+    pub async fn login_with_session(
+        &mut self,
+        session: AuthenticatedSessionMode,
+    ) -> Result<(), SplunkError> {
+        self.auth_session_mode = session;
+        if let Err(err) = self.get_current_context().await {
+            self.auth_session_mode = AuthenticatedSessionMode::Unset;
+            return Err(err);
+        }
+        debug!("Using existing session for {}", self.serverconfig.hostname);
+        Ok(())
+    }
+
+    /// Create an auth token for the logged in user, to use with [crate::ServerConfig::with_token].
+    ///
+    /// `expires_on` is relative like `+30d` or an absolute time, and without it the token doesn't expire. See
+    /// [SplunkClient::create_token] for more options.
+    pub async fn create_auth_token(
+        &self,
+        audience: &str,
+        expires_on: Option<&str>,
+    ) -> Result<Secret, SplunkError> {
+        let username = self.get_current_context().await?.username;
+        let mut token = NewToken::new(&username, audience);
+        if let Some(expires_on) = expires_on {
+            token = token.with_expires_on(expires_on);
+        }
+        Ok(self.create_token(token).await?.token)
+    }
+}
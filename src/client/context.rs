@@ -0,0 +1,110 @@
+//! Who's logged in and what they can do
+//!
+//! [SplunkClient::get_current_context] says who the session belongs to, and what their roles and capabilities are,
+//! so you can check before trying something that needs a capability:
+//!
+//! ```no_run
+//! use splunk::client::SplunkClient;
+//! # async fn example(mut client: SplunkClient) -> Result<(), splunk::errors::SplunkError> {
+//! let context = client.get_current_context().await?;
+//! if !context.has_capability("edit_indexes") {
+//!     eprintln!("{} can't edit indexes, ask for the admin role", context.username);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use super::{non_empty, splunk_bool, Entries, SplunkClient};
+use crate::errors::SplunkError;
+
+const CURRENT_CONTEXT_ENDPOINT: &str = "/services/authentication/current-context";
+const CAPABILITIES_ENDPOINT: &str = "/services/authorization/capabilities";
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+/// The logged in user, from [SplunkClient::get_current_context]
+pub struct CurrentContext {
+    /// Their username
+    pub username: String,
+    /// Their full name
+    #[serde(default)]
+    pub realname: String,
+    /// Their email address
+    #[serde(default)]
+    pub email: String,
+    /// The roles they have
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// The app they start in
+    #[serde(rename = "defaultApp", default)]
+    pub default_app: String,
+    /// Everything they can do, from all their roles
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// Their time zone, if they've set one
+    #[serde(default, deserialize_with = "non_empty")]
+    pub tz: Option<String>,
+    /// Have they been locked out after too many failed logins?
+    #[serde(rename = "lockedOut", default, deserialize_with = "splunk_bool")]
+    pub locked_out: bool,
+}
+
+impl CurrentContext {
+    /// Can they do this? Capabilities are names like `edit_indexes` or `rtsearch`
+    pub fn has_capability(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|have| have == capability)
+    }
+
+    /// Do they have this role?
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|have| have == role)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+/// Every capability the server knows about, from [SplunkClient::get_capabilities]
+pub struct Capabilities {
+    /// The capability names
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+}
+
+impl Capabilities {
+    /// Does the server have this capability? This doesn't mean the logged in user has it, for that see
+    /// [CurrentContext::has_capability]
+    pub fn has_capability(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|have| have == capability)
+    }
+}
+
+impl SplunkClient {
+    /// Get the authenticated session's user, with their roles and capabilities
+    ///
+    /// <https://docs.splunk.com/Documentation/Splunk/latest/RESTREF/RESTaccess#authentication.2Fcurrent-context>
+    pub async fn get_current_context(&self) -> Result<CurrentContext, SplunkError> {
+        let response: Entries<CurrentContext> = self
+            .request(Method::GET, CURRENT_CONTEXT_ENDPOINT)
+            .send_json()
+            .await?;
+        Ok(response.first(CURRENT_CONTEXT_ENDPOINT)?.content)
+    }
+
+    /// Get all the capabilities on the server - to check what the logged in user can do, use
+    /// [SplunkClient::has_capability]
+    ///
+    /// <https://docs.splunk.com/Documentation/Splunk/latest/RESTREF/RESTaccess#authorization.2Fcapabilities>
+    pub async fn get_capabilities(&self) -> Result<Capabilities, SplunkError> {
+        let response: Entries<Capabilities> = self
+            .request(Method::GET, CAPABILITIES_ENDPOINT)
+            .send_json()
+            .await?;
+        Ok(response.first(CAPABILITIES_ENDPOINT)?.content)
+    }
+
+    /// Can the logged in user do this? See [CurrentContext::has_capability]
+    pub async fn has_capability(&self, capability: &str) -> Result<bool, SplunkError> {
+        Ok(self.get_current_context().await?.has_capability(capability))
+    }
+}
@@ -2,51 +2,82 @@
 //!
 
 use crate::errors::SplunkError;
+use crate::retry::RetryPolicy;
+use crate::secret::Secret;
 use crate::ServerConfig;
-use reqwest::header::HeaderMap;
-use reqwest::{Client, Response};
+use log::debug;
+use reqwest::{Client, Method, Response};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
+use std::fmt::Display;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+pub mod access;
+pub mod auth;
+pub mod context;
+pub mod request;
+pub mod tokens;
+
+pub use access::{Role, User};
+pub use auth::PasscodeHook;
+pub use context::{Capabilities, CurrentContext};
+pub use request::{ApiMessage, OutputMode, RestRequest};
+pub use tokens::{CreatedToken, NewToken, TokenInfo, TokenStatus};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
-/// The current auth method for the search client
+/// The current auth method for the search client - credentials are [Secret]s, so they're left out when it's serialized
 pub enum AuthenticationMethod {
     /// Basic auth
     Basic {
         /// username
         username: String,
         ///password
-        password: String,
+        #[serde(skip_serializing, default)]
+        password: Secret,
     },
     /// Token auth
     Token {
         /// token auth
-        token: String,
+        #[serde(skip_serializing, default)]
+        token: Secret,
     },
     /// Cookie based
     Cookie {
         /// cookie store
-        cookie: HashMap<String, String>,
+        #[serde(skip_serializing, default)]
+        cookie: HashMap<String, Secret>,
     },
     /// we haven't set it yet
     Unknown,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-/// the current auth mode - you can auth with username/password then get a cookie and go from there
+#[derive(Debug, Default, Deserialize, Serialize)]
+/// the current auth mode - you can auth with username/password then get a cookie and go from there.
+///
+/// Session keys are [Secret]s, so they're left out when it's serialized.
 pub enum AuthenticatedSessionMode {
     /// cookie auth
     Cookie {
         /// cookie value
-        value: HashMap<String, String>,
+        #[serde(skip_serializing, default)]
+        value: HashMap<String, Secret>,
     },
-    /// token auth
+    /// token auth, a session key sent as `Authorization: Splunk <key>`
     Token(
         /// the token
-        String,
+        #[serde(skip)]
+        Secret,
+    ),
+    /// an auth token sent as `Authorization: Bearer <token>`
+    Bearer(
+        /// the token
+        #[serde(skip)]
+        Secret,
     ),
     /// we haven't set it yet
+    #[default]
     Unset,
 }
 
@@ -60,6 +91,15 @@ pub struct SplunkClient {
     pub auth_session_mode: AuthenticatedSessionMode,
     #[serde(skip)]
     client: Client,
+    #[serde(skip, default = "RetryPolicy::none")]
+    /// How to retry failed requests, defaults to [RetryPolicy::none]
+    retry_policy: RetryPolicy,
+    #[serde(skip, default = "crate::default_useragent")]
+    /// The user-agent string to send, defaults to `splunk-rs <version>`
+    useragent: String,
+    #[serde(skip)]
+    /// Asks for an MFA passcode on login
+    passcode_hook: Option<PasscodeHook>,
 }
 
 impl Default for SplunkClient {
@@ -68,6 +108,9 @@ impl Default for SplunkClient {
             serverconfig: ServerConfig::default(),
             auth_session_mode: AuthenticatedSessionMode::Unset,
             client: Client::new(),
+            retry_policy: RetryPolicy::none(),
+            useragent: crate::default_useragent(),
+            passcode_hook: None,
         }
     }
 }
@@ -75,12 +118,7 @@ impl Default for SplunkClient {
 impl SplunkClient {
     /// set the config on build
     pub fn with_config(self, serverconfig: ServerConfig) -> Result<Self, SplunkError> {
-        let client = match serverconfig.verify_tls {
-            true => Client::new(),
-            false => Client::builder()
-                .danger_accept_invalid_certs(true)
-                .build()?,
-        };
+        let client = serverconfig.http_client()?;
 
         Ok(Self {
             serverconfig,
@@ -97,55 +135,99 @@ impl SplunkClient {
         }
     }
 
-    /// Make a POST request
+    /// Set the [RetryPolicy] used for requests
+    pub fn with_retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Self {
+            retry_policy,
+            ..self
+        }
+    }
+
+    /// Configure a custom user-agent string
+    pub fn with_useragent(self, useragent: &str) -> Self {
+        Self {
+            useragent: useragent.to_string(),
+            ..self
+        }
+    }
+
+    /// Start a request to the REST API, which goes through the [request] pipeline - authenticated with the session
+    /// if there is one, retried with the [RetryPolicy]
+    pub fn request(&self, method: Method, endpoint: &str) -> RestRequest<'_> {
+        let request = RestRequest::new(&self.serverconfig, method, endpoint)
+            .with_client(&self.client)
+            .with_retry_policy(&self.retry_policy)
+            .useragent(&self.useragent);
+        match self.auth_session_mode {
+            AuthenticatedSessionMode::Unset => request,
+            _ => request.with_session(&self.auth_session_mode),
+        }
+    }
+
+    /// Make a POST request, with the total timeout from [ServerConfig::timeouts]
     pub async fn do_post(
         &mut self,
         endpoint: &str,
-        payload: HashMap<impl Serialize, String>,
+        payload: HashMap<impl ToString, String>,
+    ) -> Result<Response, SplunkError> {
+        self.request(Method::POST, endpoint)
+            .form(payload)
+            .send()
+            .await
+    }
+
+    /// Make a POST request, overriding the total timeout - `None` lets it run as long as it needs
+    pub async fn do_post_with_timeout(
+        &mut self,
+        endpoint: &str,
+        payload: HashMap<impl ToString, String>,
+        timeout: Option<Duration>,
     ) -> Result<Response, SplunkError> {
-        let req = self
-            .client
-            .post(self.serverconfig.get_url(endpoint)?)
-            .form(&payload);
+        self.request(Method::POST, endpoint)
+            .form(payload)
+            .timeout(timeout)
+            .send()
+            .await
+    }
 
-        let req = match &self.serverconfig.auth_method {
-            AuthenticationMethod::Basic { username, password } => {
-                req.basic_auth(username, Some(password))
-            }
-            AuthenticationMethod::Token { token } => {
-                req.header("Authorization", format!("Splunk {}", token))
-            }
-            AuthenticationMethod::Unknown => return Err(SplunkError::NotAuthenticated),
-            // TODO: handle cookie auth for posts?
-            AuthenticationMethod::Cookie { cookie: _ } => req,
-        };
-        req.send()
+    /// Make a PUT request
+    pub async fn do_put(
+        &mut self,
+        endpoint: &str,
+        payload: HashMap<impl ToString, String>,
+    ) -> Result<Response, SplunkError> {
+        self.request(Method::PUT, endpoint)
+            .form(payload)
+            .send()
             .await
-            .map(|val| val.error_for_status().map_err(SplunkError::ReqwestError))?
+    }
+
+    /// Make a DELETE request
+    pub async fn do_delete(&mut self, endpoint: &str) -> Result<Response, SplunkError> {
+        self.request(Method::DELETE, endpoint).send().await
     }
 
     /// Make a GET request, tries to pass the authentication automagically
     pub async fn do_get(&mut self, endpoint: &str) -> Result<Response, SplunkError> {
-        let request = self.client.get(self.serverconfig.get_url(endpoint)?);
-
-        let request = match &self.auth_session_mode {
-            AuthenticatedSessionMode::Token(value) => {
-                let mut headers = HeaderMap::new();
-                headers.insert("Authorization", format!("Splunk {}", value).parse()?);
-                request.headers(headers)
-            }
-            AuthenticatedSessionMode::Cookie { value: _ } => request,
-            AuthenticatedSessionMode::Unset => return Err(SplunkError::NotAuthenticated),
-        };
+        self.request(Method::GET, endpoint).send().await
+    }
 
-        // eprintln!("{:#?}", request);
-        request
+    /// Make a GET request, overriding the total timeout - `None` lets it run as long as it needs
+    pub async fn do_get_with_timeout(
+        &mut self,
+        endpoint: &str,
+        timeout: Option<Duration>,
+    ) -> Result<Response, SplunkError> {
+        self.request(Method::GET, endpoint)
+            .timeout(timeout)
             .send()
             .await
-            .map_err(|e| SplunkError::Generic(format!("{e:?}")))
     }
 
-    /// Login and establish the session
+    /// Login and establish the session.
+    ///
+    /// With a token or cookie there's nothing to log in to, so they're used as the session. With a username and
+    /// password, the [PasscodeHook] is asked for an MFA passcode if there is one.
     pub async fn login(&mut self) -> Result<(), SplunkError> {
         let endpoint = "/services/auth/login";
 
@@ -153,23 +235,36 @@ impl SplunkClient {
 
         match &self.serverconfig.auth_method {
             AuthenticationMethod::Basic { username, password } => {
-                // request.basic_auth(username, Some(password)),
                 payload.insert("username".to_string(), username.to_owned());
-                payload.insert("password".to_string(), password.to_owned());
+                payload.insert("password".to_string(), password.expose().to_owned());
+                if let Some(hook) = &self.passcode_hook {
+                    let passcode = hook.passcode(username)?;
+                    payload.insert("passcode".to_string(), passcode.expose().to_owned());
+                }
+            }
+            AuthenticationMethod::Token { token } => {
+                self.auth_session_mode = AuthenticatedSessionMode::Token(token.clone());
+                return Ok(());
+            }
+            AuthenticationMethod::Cookie { cookie } => {
+                self.auth_session_mode = AuthenticatedSessionMode::Cookie {
+                    value: cookie.clone(),
+                };
+                return Ok(());
             }
-            // AuthenticationMethod::Token { token } => todo!(),
             AuthenticationMethod::Unknown => return Err(SplunkError::NoAuthMethodSelected),
-            #[allow(clippy::todo)]
-            _ => todo!("Token mode isn't supported!"),
         };
 
-        let request = self.do_post(endpoint, payload).await?;
+        let request = self
+            .request(Method::POST, endpoint)
+            .unauthenticated()
+            .form(payload)
+            .send()
+            .await?;
 
         #[cfg(test)]
         eprintln!("Headers: {:#?}", request.headers());
-        let body = request.text().await?;
-        #[cfg(test)]
-        eprintln!("Body: {}", body);
+        let body = Zeroizing::new(request.text().await?);
         let res: SessionKey = serde_xml_rs::from_str(&body)?;
 
         #[derive(Deserialize)]
@@ -181,38 +276,12 @@ impl SplunkClient {
             Some(val) => val,
             None => return Err(SplunkError::Generic("Couldn't get sessionKey".to_string())),
         };
-        eprintln!("Body parsing OK");
+        debug!("Logged in to {}", self.serverconfig.hostname);
 
-        self.auth_session_mode = AuthenticatedSessionMode::Token(res);
+        self.auth_session_mode = AuthenticatedSessionMode::Token(Secret::new(res));
         Ok(())
     }
 
-    /// Get the authenticated session owner username.
-    /// <https://docs.splunk.com/Documentation/Splunk/9.0.4/RESTREF/RESTaccess#authentication.2Fcurrent-context>
-    /// Currently returns just the raw XML result as a string
-    pub async fn get_current_context(&mut self) -> Result<String, SplunkError> {
-        let endpoint = "/services/authentication/current-context";
-
-        let res = self.do_get(endpoint).await?;
-        let res = res.text().await.map_err(|e| format!("{e:?}"))?;
-        Ok(res)
-    }
-
-    /// Get the authenticated session owner username.
-    /// <https://docs.splunk.com/Documentation/Splunk/9.0.4/RESTREF/RESTaccess#authorization.2Fcapabilities>
-    ///
-    /// Currently returns just the raw XML result as a string
-    pub async fn get_capabilities(&mut self) -> Result<String, SplunkError> {
-        let endpoint = "/services/authorization/capabilities";
-
-        let res = self.do_get(endpoint).await?;
-        let res = res
-            .text()
-            .await
-            .map_err(|e| SplunkError::Generic(format!("{e:?}")))?;
-        Ok(res)
-    }
-
     /// Get the saved searches from an instance
     ///
     /// This returns a [serde_json::Value] because it's a big complex mess of JSON with variable fields
@@ -255,7 +324,11 @@ impl SplunkClient {
         // TODO: this is janky
         add_query_params_to_endpoint(&mut endpoint, &params);
 
-        let res = self.do_get(&endpoint).await?;
+        let res = self
+            .request(Method::GET, &endpoint)
+            .namespaced()
+            .send()
+            .await?;
         // do the query
         let res_content = res.text().await.map_err(|err| {
             SplunkError::Generic(format!(
@@ -318,17 +391,17 @@ impl SplunkClient {
     }
 }
 
-/// Takes a HashMap of key/value pairs to add ot the URL and adds the query values to the endpoint
-pub(crate) fn add_query_params_to_endpoint(
+/// Takes key/value pairs (a HashMap, or a Vec when the order matters) and adds them to the endpoint as query values
+pub(crate) fn add_query_params_to_endpoint<K: Display, V: ToString>(
     endpoint: &mut String,
-    params: &HashMap<&str, impl ToString>,
+    params: impl IntoIterator<Item = (K, V)>,
 ) {
-    if !params.is_empty() {
+    let param_strings: Vec<String> = params
+        .into_iter()
+        .map(|(k, v)| format!("{}={}", k, urlencoding::Encoded(v.to_string())))
+        .collect();
+    if !param_strings.is_empty() {
         endpoint.push('?');
-        let param_strings: Vec<String> = params
-            .iter()
-            .map(|(k, v)| format!("{}={}", k, urlencoding::Encoded(v.to_string())))
-            .collect();
         endpoint.push_str(&param_strings.join("&"));
     }
 }
@@ -394,3 +467,71 @@ impl ApiResponse {
         }
     }
 }
+
+/// The `entry` list of a JSON API response, with each entry's `content` parsed as `T`
+#[derive(Deserialize, Debug)]
+pub(crate) struct Entries<T> {
+    #[serde(default = "Vec::new")]
+    pub entry: Vec<Entry<T>>,
+}
+
+/// One entry in a JSON API response
+#[derive(Deserialize, Debug)]
+pub(crate) struct Entry<T> {
+    /// What the entry's called - a username, role, token id and so on
+    pub name: String,
+    pub content: T,
+}
+
+impl<T> Entries<T> {
+    /// The first entry, for endpoints which return one thing
+    pub fn first(self, endpoint: &str) -> Result<Entry<T>, SplunkError> {
+        self.entry
+            .into_iter()
+            .next()
+            .ok_or_else(|| SplunkError::Generic(format!("No entry in response from {endpoint}")))
+    }
+}
+
+/// Empty strings are Splunk's way of saying "not set"
+pub(crate) fn non_empty<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.filter(|value| !value.is_empty()))
+}
+
+/// Splunk's booleans come back as `true`, `1` or `"1"` depending on the endpoint
+pub(crate) fn splunk_bool<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<bool, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Bool(value) => Ok(value),
+        Value::Number(value) => Ok(value.as_i64() != Some(0)),
+        Value::String(value) => match value.to_ascii_lowercase().as_str() {
+            "1" | "true" | "t" | "yes" | "y" | "on" | "enabled" => Ok(true),
+            "0" | "false" | "f" | "no" | "n" | "off" | "disabled" | "" => Ok(false),
+            _ => Err(serde::de::Error::custom(format!("not a boolean: {value}"))),
+        },
+        Value::Null => Ok(false),
+        value => Err(serde::de::Error::custom(format!("not a boolean: {value}"))),
+    }
+}
+
+/// Splunk's numbers sometimes come back as strings, and an empty one means it's not set
+pub(crate) fn splunk_int<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<i64>, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Number(value) => value
+            .as_i64()
+            .map(Some)
+            .ok_or_else(|| serde::de::Error::custom(format!("not an integer: {value}"))),
+        Value::String(value) if value.is_empty() => Ok(None),
+        Value::String(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| serde::de::Error::custom(format!("not an integer: {value}"))),
+        Value::Null => Ok(None),
+        value => Err(serde::de::Error::custom(format!("not an integer: {value}"))),
+    }
+}
@@ -0,0 +1,398 @@
+//! The pipeline every REST call goes through
+//!
+//! Build a [RestRequest] with [SplunkClient::request] (or use the `do_*` helpers), and when it's sent:
+//!
+//! 1. the endpoint is put under `/servicesNS/<owner>/<app>` if it's [RestRequest::namespaced], see
+//!    [ServerConfig::with_namespace]
+//! 2. `output_mode` is added to the query string if one's set
+//! 3. credentials are added - the session from [SplunkClient::login] if there is one, otherwise the
+//!    [AuthenticationMethod]
+//! 4. the user agent and total timeout are set
+//! 5. it's sent (and retried) with the client's [RetryPolicy], and logged - a `POST` is only retried when it couldn't
+//!    connect, unless it's [RestRequest::idempotent]
+//! 6. an error status becomes [SplunkError::ApiError], with the messages from the response body
+//!
+//! ```no_run
+//! use reqwest::Method;
+//! use splunk::client::{OutputMode, SplunkClient};
+//! # async fn example(client: SplunkClient) -> Result<(), splunk::errors::SplunkError> {
+//! let response: serde_json::Value = client
+//!     .request(Method::GET, "/services/saved/searches")
+//!     .namespaced()
+//!     .query("count", "0")
+//!     .output_mode(OutputMode::Json)
+//!     .send_json()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use log::debug;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE, COOKIE, USER_AGENT};
+use reqwest::{Client, Method, Response};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+#[cfg(doc)]
+use super::SplunkClient;
+use super::{AuthenticatedSessionMode, AuthenticationMethod};
+use crate::errors::SplunkError;
+use crate::http::with_total_timeout;
+use crate::retry::RetryPolicy;
+use crate::secret::Secret;
+use crate::ServerConfig;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// The format to ask the REST API for
+pub enum OutputMode {
+    /// `output_mode=json`
+    #[default]
+    Json,
+    /// `output_mode=xml`, the server's default
+    Xml,
+}
+
+impl Display for OutputMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputMode::Json => write!(f, "json"),
+            OutputMode::Xml => write!(f, "xml"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+/// A message from the server, like the reason a request failed
+pub struct ApiMessage {
+    /// `ERROR`, `WARN`, `INFO` and so on
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    /// What it says
+    #[serde(default)]
+    pub text: String,
+}
+
+impl ApiMessage {
+    /// Pull the messages out of an error response, which can be JSON or XML. If it's neither, the whole body is the
+    /// message.
+    pub fn parse(body: &str) -> Vec<Self> {
+        #[derive(Deserialize)]
+        struct JsonMessages {
+            messages: Vec<ApiMessage>,
+        }
+        #[derive(Deserialize)]
+        struct XmlResponse {
+            messages: XmlMessages,
+        }
+        #[derive(Deserialize)]
+        struct XmlMessages {
+            #[serde(default)]
+            msg: Vec<XmlMessage>,
+        }
+        #[derive(Deserialize)]
+        struct XmlMessage {
+            #[serde(rename = "@type", default)]
+            kind: String,
+            #[serde(rename = "#text", default)]
+            text: String,
+        }
+
+        if let Ok(response) = serde_json::from_str::<JsonMessages>(body) {
+            return response.messages;
+        }
+        if let Ok(response) = serde_xml_rs::from_str::<XmlResponse>(body) {
+            return response
+                .messages
+                .msg
+                .into_iter()
+                .map(|msg| ApiMessage {
+                    kind: msg.kind,
+                    text: msg.text,
+                })
+                .collect();
+        }
+        match body.trim() {
+            "" => Vec::new(),
+            body => vec![ApiMessage {
+                kind: "ERROR".to_string(),
+                text: body.to_string(),
+            }],
+        }
+    }
+}
+
+/// What's sent in the request body
+enum Body {
+    Empty,
+    Form(Vec<(String, String)>),
+    Json(Bytes),
+}
+
+/// A request to the REST API, see the [module documentation](self)
+pub struct RestRequest<'a> {
+    serverconfig: &'a ServerConfig,
+    client: Option<&'a Client>,
+    session: Option<&'a AuthenticatedSessionMode>,
+    retry_policy: Option<&'a RetryPolicy>,
+    useragent: String,
+    method: Method,
+    endpoint: String,
+    query: Vec<(String, String)>,
+    headers: HeaderMap,
+    body: Body,
+    output_mode: Option<OutputMode>,
+    namespaced: bool,
+    authenticated: bool,
+    idempotent: bool,
+    timeout: Option<Duration>,
+}
+
+impl<'a> RestRequest<'a> {
+    /// Start a request to an endpoint on the server, authenticated with its [AuthenticationMethod]
+    pub fn new(serverconfig: &'a ServerConfig, method: Method, endpoint: &str) -> Self {
+        let idempotent = [Method::GET, Method::HEAD, Method::PUT, Method::DELETE].contains(&method);
+        Self {
+            serverconfig,
+            client: None,
+            session: None,
+            retry_policy: None,
+            useragent: crate::default_useragent(),
+            method,
+            endpoint: endpoint.to_string(),
+            query: Vec::new(),
+            headers: HeaderMap::new(),
+            body: Body::Empty,
+            output_mode: None,
+            namespaced: false,
+            authenticated: true,
+            idempotent,
+            timeout: serverconfig.timeouts().total,
+        }
+    }
+
+    /// Send with this client, rather than the one from the [ServerConfig]
+    pub(crate) fn with_client(mut self, client: &'a Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Authenticate with this session, rather than the [AuthenticationMethod]
+    pub(crate) fn with_session(mut self, session: &'a AuthenticatedSessionMode) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    /// Retry with this policy, rather than not at all
+    pub(crate) fn with_retry_policy(mut self, retry_policy: &'a RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// Send this user agent
+    pub fn useragent(mut self, useragent: &str) -> Self {
+        self.useragent = useragent.to_string();
+        self
+    }
+
+    /// Add a query string parameter
+    pub fn query(mut self, key: &str, value: impl Into<String>) -> Self {
+        self.query.push((key.to_string(), value.into()));
+        self
+    }
+
+    /// Add a header
+    pub fn header(mut self, name: &'static str, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Add headers
+    pub fn headers(mut self, headers: HeaderMap) -> Self {
+        self.headers.extend(headers);
+        self
+    }
+
+    /// Send a form-encoded body, which is what most endpoints take
+    pub fn form<K: ToString, V: ToString>(
+        mut self,
+        form: impl IntoIterator<Item = (K, V)>,
+    ) -> Self {
+        self.body = Body::Form(
+            form.into_iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        );
+        self
+    }
+
+    /// Send a JSON body, for endpoints like the KV store
+    pub fn json(mut self, body: &impl Serialize) -> Result<Self, SplunkError> {
+        self.body = Body::Json(serde_json::to_vec(body)?.into());
+        Ok(self)
+    }
+
+    /// Ask for the response in this format
+    pub fn output_mode(mut self, output_mode: OutputMode) -> Self {
+        self.output_mode = Some(output_mode);
+        self
+    }
+
+    /// Put the endpoint in the owner/app namespace from [ServerConfig::with_namespace]
+    pub fn namespaced(mut self) -> Self {
+        self.namespaced = true;
+        self
+    }
+
+    /// Don't send credentials, for logging in
+    pub fn unauthenticated(mut self) -> Self {
+        self.authenticated = false;
+        self
+    }
+
+    /// Sending this more than once does no harm, so retry it on timeouts and retryable statuses too.
+    ///
+    /// `GET`, `HEAD`, `PUT` and `DELETE` requests already are. Anything else (like a `POST` that creates a token, user
+    /// or search job) is only retried when it couldn't connect, so it's never created twice.
+    pub fn idempotent(mut self) -> Self {
+        self.idempotent = true;
+        self
+    }
+
+    /// Override the total timeout - `None` lets it run as long as it needs
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Send the request, returning the response if the status was a success
+    pub async fn send(mut self) -> Result<Response, SplunkError> {
+        let endpoint = match self.namespaced {
+            true => self.serverconfig.namespaced_endpoint(&self.endpoint),
+            false => self.endpoint.clone(),
+        };
+        let mut url = self.serverconfig.get_url(&endpoint)?;
+        if let Some(output_mode) = self.output_mode {
+            if !url.query_pairs().any(|(key, _)| key == "output_mode") {
+                self.query
+                    .push(("output_mode".to_string(), output_mode.to_string()));
+            }
+        }
+        if !self.query.is_empty() {
+            url.query_pairs_mut().extend_pairs(&self.query);
+        }
+
+        let mut headers = self.headers.clone();
+        headers.insert(USER_AGENT, HeaderValue::try_from(&self.useragent)?);
+        let basic_auth = match self.authenticated {
+            true => self.authenticate(&mut headers)?,
+            false => None,
+        };
+
+        let client = match self.client {
+            Some(client) => client.clone(),
+            None => self.serverconfig.http_client()?,
+        };
+        let no_retries = RetryPolicy::none();
+        let retry_policy = self.retry_policy.unwrap_or(&no_retries);
+        debug!("{} {}", self.method, url);
+        let started = Instant::now();
+        let send = || {
+            let mut request = with_total_timeout(
+                client.request(self.method.clone(), url.clone()),
+                self.timeout,
+            )
+            .headers(headers.clone());
+            if let Some((username, password)) = basic_auth {
+                request = request.basic_auth(username, Some(password.expose()));
+            }
+            match &self.body {
+                Body::Empty => request,
+                Body::Form(form) => request.form(form),
+                Body::Json(body) => request
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(body.clone()),
+            }
+            .send()
+        };
+        let response = match self.idempotent {
+            true => retry_policy.execute(send).await?,
+            false => retry_policy.execute_non_idempotent(send).await?,
+        };
+
+        let status = response.status();
+        debug!(
+            "{} {} returned {} in {:?}",
+            self.method,
+            url.path(),
+            status,
+            started.elapsed()
+        );
+        if !status.is_success() {
+            let body = response.text().await?;
+            return Err(SplunkError::ApiError {
+                status: status.as_u16(),
+                messages: ApiMessage::parse(&body),
+            });
+        }
+        Ok(response)
+    }
+
+    /// Send the request asking for JSON (unless another [OutputMode] is set), and parse the response
+    pub async fn send_json<T: DeserializeOwned>(mut self) -> Result<T, SplunkError> {
+        self.output_mode.get_or_insert(OutputMode::Json);
+        let body = self.send().await?.text().await?;
+        serde_json::from_str(&body).map_err(SplunkError::from)
+    }
+
+    /// Add the credentials to the headers, or return them for basic auth
+    fn authenticate(
+        &self,
+        headers: &mut HeaderMap,
+    ) -> Result<Option<(&'a str, &'a Secret)>, SplunkError> {
+        match self.session {
+            Some(AuthenticatedSessionMode::Token(session_key)) => {
+                headers.insert(AUTHORIZATION, session_key.auth_header("Splunk")?);
+                return Ok(None);
+            }
+            Some(AuthenticatedSessionMode::Bearer(token)) => {
+                headers.insert(AUTHORIZATION, token.auth_header("Bearer")?);
+                return Ok(None);
+            }
+            Some(AuthenticatedSessionMode::Cookie { value }) if !value.is_empty() => {
+                headers.insert(COOKIE, cookie_header(value)?);
+                return Ok(None);
+            }
+            _ => {}
+        }
+        let serverconfig: &'a ServerConfig = self.serverconfig;
+        match &serverconfig.auth_method {
+            AuthenticationMethod::Token { token } => {
+                headers.insert(AUTHORIZATION, token.auth_header("Splunk")?);
+                Ok(None)
+            }
+            AuthenticationMethod::Basic { username, password } => Ok(Some((username, password))),
+            AuthenticationMethod::Cookie { cookie } => {
+                headers.insert(COOKIE, cookie_header(cookie)?);
+                Ok(None)
+            }
+            AuthenticationMethod::Unknown => Err(SplunkError::NotAuthenticated),
+        }
+    }
+}
+
+/// A `Cookie` header value, marked sensitive so reqwest won't log it
+fn cookie_header(cookies: &HashMap<String, Secret>) -> Result<HeaderValue, SplunkError> {
+    let value = cookies
+        .iter()
+        .map(|(name, value)| format!("{name}={}", value.expose()))
+        .collect::<Vec<_>>()
+        .join("; ");
+    let mut value = HeaderValue::try_from(value)?;
+    value.set_sensitive(true);
+    Ok(value)
+}
@@ -0,0 +1,271 @@
+//! Managing auth tokens, through `authorization/tokens`
+//!
+//! Create tokens with [SplunkClient::create_token], find them with [SplunkClient::list_tokens], and switch them off
+//! or remove them with [SplunkClient::disable_token] and [SplunkClient::delete_token]. Token authentication has to
+//! be turned on for the server, which [SplunkClient::token_auth_enabled] checks.
+//!
+//! Rotating a service account's token:
+//!
+//! ```no_run
+//! use splunk::client::{NewToken, SplunkClient};
+//! # async fn example(mut client: SplunkClient) -> Result<(), splunk::errors::SplunkError> {
+//! let old_tokens = client.list_tokens(Some("svc_ingest")).await?;
+//! let created = client
+//!     .create_token(NewToken::new("svc_ingest", "ingest pipeline").with_expires_on("+35d"))
+//!     .await?;
+//! // ... store created.token somewhere safe, then
+//! for token in old_tokens.iter().filter(|token| token.audience == "ingest pipeline") {
+//!     client.delete_token(&token.owner, &token.id).await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! <https://docs.splunk.com/Documentation/Splunk/latest/RESTREF/RESTaccess#authorization.2Ftokens>
+
+use std::fmt::Display;
+
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use super::{splunk_bool, Entries, SplunkClient};
+use crate::errors::SplunkError;
+use crate::secret::Secret;
+
+const TOKENS_ENDPOINT: &str = "/services/authorization/tokens";
+const TOKEN_AUTH_ENDPOINT: &str = "/services/admin/token-auth/tokens_auth";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A token to create with [SplunkClient::create_token]
+pub struct NewToken {
+    /// The user the token authenticates as
+    pub name: String,
+    /// What the token's for
+    pub audience: String,
+    /// When it expires, relative like `+30d` or an absolute time - without it the token doesn't expire
+    pub expires_on: Option<String>,
+    /// When it starts working, in the same formats as `expires_on`
+    pub not_before: Option<String>,
+}
+
+impl NewToken {
+    /// A token for `name`, which doesn't expire
+    pub fn new(name: &str, audience: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            audience: audience.to_string(),
+            expires_on: None,
+            not_before: None,
+        }
+    }
+
+    /// Set when it expires
+    pub fn with_expires_on(self, expires_on: &str) -> Self {
+        Self {
+            expires_on: Some(expires_on.to_string()),
+            ..self
+        }
+    }
+
+    /// Set when it starts working
+    pub fn with_not_before(self, not_before: &str) -> Self {
+        Self {
+            not_before: Some(not_before.to_string()),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+/// A token that's just been created - this is the only time you get to see it
+pub struct CreatedToken {
+    /// The token's id, for [SplunkClient::disable_token] and friends
+    pub id: String,
+    /// The token itself
+    pub token: Secret,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+/// Whether a token can be used
+pub enum TokenStatus {
+    /// It works
+    #[default]
+    Enabled,
+    /// It's been switched off
+    Disabled,
+}
+
+impl Display for TokenStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenStatus::Enabled => write!(f, "enabled"),
+            TokenStatus::Disabled => write!(f, "disabled"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+/// What the server knows about a token, from [SplunkClient::list_tokens]
+pub struct TokenInfo {
+    /// The token's id
+    pub id: String,
+    /// The user it authenticates as
+    pub owner: String,
+    /// What it's for
+    pub audience: String,
+    /// Whether it can be used
+    pub status: TokenStatus,
+    /// Who created it, and where
+    pub issuer: String,
+    /// When it was created, in seconds since the epoch
+    pub issued_at: i64,
+    /// When it expires, in seconds since the epoch
+    pub expires_at: Option<i64>,
+    /// When it starts working, in seconds since the epoch
+    pub not_before: Option<i64>,
+    /// When it was last used, in seconds since the epoch
+    pub last_used: Option<i64>,
+    /// Where it was last used from
+    pub last_used_ip: Option<String>,
+}
+
+impl TokenInfo {
+    /// Has it expired, as of `now` (in seconds since the epoch)?
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenContent {
+    claims: TokenClaims,
+    #[serde(default)]
+    status: TokenStatus,
+    #[serde(rename = "lastUsed", default)]
+    last_used: Option<i64>,
+    #[serde(rename = "lastUsedIp", default)]
+    last_used_ip: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenClaims {
+    #[serde(default)]
+    sub: String,
+    #[serde(default)]
+    aud: String,
+    #[serde(default)]
+    iss: String,
+    #[serde(default)]
+    iat: i64,
+    #[serde(default)]
+    exp: Option<i64>,
+    #[serde(default)]
+    nbr: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct TokenAuthContent {
+    #[serde(deserialize_with = "splunk_bool", default)]
+    disabled: bool,
+}
+
+/// Zero means "not set" in token timestamps
+fn nonzero(value: Option<i64>) -> Option<i64> {
+    value.filter(|value| *value != 0)
+}
+
+impl SplunkClient {
+    /// Create an auth token, see the [module documentation](self)
+    pub async fn create_token(&self, token: NewToken) -> Result<CreatedToken, SplunkError> {
+        let mut payload = vec![("name", token.name), ("audience", token.audience)];
+        if let Some(expires_on) = token.expires_on {
+            payload.push(("expires_on", expires_on));
+        }
+        if let Some(not_before) = token.not_before {
+            payload.push(("not_before", not_before));
+        }
+        let response: Entries<CreatedToken> = self
+            .request(Method::POST, TOKENS_ENDPOINT)
+            .form(payload)
+            .send_json()
+            .await?;
+        Ok(response.first(TOKENS_ENDPOINT)?.content)
+    }
+
+    /// List the tokens you can see, or just the ones belonging to `username`
+    pub async fn list_tokens(&self, username: Option<&str>) -> Result<Vec<TokenInfo>, SplunkError> {
+        let mut request = self
+            .request(Method::GET, TOKENS_ENDPOINT)
+            .query("count", "0");
+        if let Some(username) = username {
+            request = request.query("username", username);
+        }
+        let response: Entries<TokenContent> = request.send_json().await?;
+        Ok(response
+            .entry
+            .into_iter()
+            .map(|entry| TokenInfo {
+                id: entry.name,
+                owner: entry.content.claims.sub,
+                audience: entry.content.claims.aud,
+                status: entry.content.status,
+                issuer: entry.content.claims.iss,
+                issued_at: entry.content.claims.iat,
+                expires_at: nonzero(entry.content.claims.exp),
+                not_before: nonzero(entry.content.claims.nbr),
+                last_used: nonzero(entry.content.last_used),
+                last_used_ip: entry.content.last_used_ip.filter(|ip| !ip.is_empty()),
+            })
+            .collect())
+    }
+
+    /// Enable or disable one of `username`'s tokens
+    pub async fn set_token_status(
+        &self,
+        username: &str,
+        id: &str,
+        status: TokenStatus,
+    ) -> Result<(), SplunkError> {
+        self.request(Method::POST, &user_tokens_endpoint(username))
+            .form([("id", id.to_string()), ("status", status.to_string())])
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// Let one of `username`'s tokens be used again
+    pub async fn enable_token(&self, username: &str, id: &str) -> Result<(), SplunkError> {
+        self.set_token_status(username, id, TokenStatus::Enabled)
+            .await
+    }
+
+    /// Stop one of `username`'s tokens working, without deleting it
+    pub async fn disable_token(&self, username: &str, id: &str) -> Result<(), SplunkError> {
+        self.set_token_status(username, id, TokenStatus::Disabled)
+            .await
+    }
+
+    /// Delete one of `username`'s tokens
+    pub async fn delete_token(&self, username: &str, id: &str) -> Result<(), SplunkError> {
+        self.request(Method::DELETE, &user_tokens_endpoint(username))
+            .query("id", id)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// Is token authentication turned on for the server?
+    pub async fn token_auth_enabled(&self) -> Result<bool, SplunkError> {
+        let response: Entries<TokenAuthContent> = self
+            .request(Method::GET, TOKEN_AUTH_ENDPOINT)
+            .send_json()
+            .await?;
+        Ok(!response.first(TOKEN_AUTH_ENDPOINT)?.content.disabled)
+    }
+}
+
+/// `authorization/tokens/<username>`
+fn user_tokens_endpoint(username: &str) -> String {
+    format!("{TOKENS_ENDPOINT}/{}", urlencoding::encode(username))
+}
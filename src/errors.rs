@@ -4,6 +4,8 @@ use std::{num::ParseIntError, time::SystemTimeError};
 
 use reqwest::header::InvalidHeaderValue;
 
+use crate::hec::HecResponse;
+
 #[derive(Debug)]
 /// Error messages and things
 pub enum SplunkError {
@@ -25,6 +27,159 @@ pub enum SplunkError {
 
     /// Invalid Auth method selected
     InvalidAuthmethod(&'static str),
+
+    /// The indexers didn't acknowledge this `ackId` in time
+    HecAckTimeout(u64),
+
+    /// Indexer acknowledgement isn't available, the message says why
+    HecAckUnavailable(String),
+
+    /// The request body (or a single event) is bigger than the maximum payload size
+    PayloadTooLarge {
+        /// Size of the payload, in bytes
+        size: usize,
+        /// The configured limit, in bytes
+        limit: usize,
+    },
+
+    /// The [crate::hec::HecSender] has been shut down and isn't accepting events
+    HecSenderClosed,
+
+    /// The metric event wouldn't be accepted by a metrics index, the message says why
+    InvalidMetric(String),
+
+    /// HEC code 1, the token is disabled
+    HecTokenDisabled(HecResponse),
+    /// HEC code 2, no token was sent
+    HecTokenRequired(HecResponse),
+    /// HEC code 3, the `Authorization` header is invalid
+    HecInvalidAuthorization(HecResponse),
+    /// HEC code 4, the token isn't valid
+    HecInvalidToken(HecResponse),
+    /// HEC code 5, the request didn't have any data in it
+    HecNoData(HecResponse),
+    /// HEC code 6, an event couldn't be parsed - see [HecResponse::invalid_event_number]
+    HecInvalidDataFormat(HecResponse),
+    /// HEC code 7, the index doesn't exist or the token isn't allowed to write to it
+    HecIncorrectIndex(HecResponse),
+    /// HEC code 8, the server had an internal error
+    HecInternalError(HecResponse),
+    /// HEC code 9, the server is busy - try again later
+    HecServerBusy(HecResponse),
+    /// HEC code 10, indexer acknowledgement or the raw endpoint needs a channel
+    HecDataChannelMissing(HecResponse),
+    /// HEC code 11, the channel isn't a valid GUID, or the server doesn't know about it
+    HecInvalidDataChannel(HecResponse),
+    /// HEC code 12, the event field is missing
+    HecEventFieldRequired(HecResponse),
+    /// HEC code 13, the event field is blank
+    HecEventFieldBlank(HecResponse),
+    /// HEC code 14, indexer acknowledgement is disabled on the token
+    HecAckDisabled(HecResponse),
+    /// HEC code 15, the indexed fields couldn't be handled
+    HecIndexedFieldsError(HecResponse),
+    /// HEC code 16, the token was sent in the query string but that isn't allowed
+    HecQueryStringAuthDisabled(HecResponse),
+    /// HEC codes 18 to 20, the server is unhealthy because its queues are full or the ack service is unavailable
+    HecUnhealthy(HecResponse),
+    /// HEC rejected the request without a code we know about
+    HecRequestFailed {
+        /// The HTTP status code
+        status: u16,
+        /// What the server sent back
+        response: HecResponse,
+    },
+
+    /// A [crate::hec::HecBalancer] needs at least one target
+    NoHecTargets,
+
+    /// Some of the requests made by [crate::hec::HecClient::send_events] or [crate::hec::HecClient::send_raw_lines]
+    /// were accepted before one failed. Only the events (or lines) after the first `sent` need sending again.
+    HecPartiallySent {
+        /// The responses to the requests which were accepted
+        responses: Vec<HecResponse>,
+        /// How many events or lines those requests held
+        sent: usize,
+        /// Why the next request failed
+        error: Box<SplunkError>,
+    },
+
+    /// Some events weren't sent by [crate::hec::HecClient::flush], the report says what happened
+    FlushFailed(Box<crate::hec::FlushReport>),
+
+    /// The server didn't answer in time, see [crate::http::Timeouts]
+    Timeout(reqwest::Error),
+
+    /// A REST API call failed
+    ApiError {
+        /// The HTTP status code
+        status: u16,
+        /// What the server said about it
+        messages: Vec<crate::client::ApiMessage>,
+    },
+}
+
+impl SplunkError {
+    /// Is it worth sending the same thing again later? True for busy or unhealthy servers, timeouts and connection
+    /// failures, false when the request itself (or the token) is the problem.
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            SplunkError::HecInternalError(_)
+            | SplunkError::HecServerBusy(_)
+            | SplunkError::HecUnhealthy(_)
+            | SplunkError::HecAckTimeout(_)
+            | SplunkError::Timeout(_) => true,
+            SplunkError::HecRequestFailed { status, .. } | SplunkError::ApiError { status, .. } => {
+                *status == 429 || *status >= 500
+            }
+            SplunkError::ReqwestError(err) => {
+                err.is_connect()
+                    || err.is_timeout()
+                    || err
+                        .status()
+                        .map(|status| status.as_u16() == 429 || status.is_server_error())
+                        .unwrap_or(false)
+            }
+            _ => false,
+        }
+    }
+
+    /// Wrap an error from part way through a multi-request send, if anything was sent before it
+    pub(crate) fn partially_sent(responses: Vec<HecResponse>, sent: usize, error: Self) -> Self {
+        match responses.is_empty() {
+            true => error,
+            false => SplunkError::HecPartiallySent {
+                responses,
+                sent,
+                error: Box::new(error),
+            },
+        }
+    }
+
+    /// The response from HEC that caused this error, if there was one
+    pub fn hec_response(&self) -> Option<&HecResponse> {
+        match self {
+            SplunkError::HecTokenDisabled(response)
+            | SplunkError::HecTokenRequired(response)
+            | SplunkError::HecInvalidAuthorization(response)
+            | SplunkError::HecInvalidToken(response)
+            | SplunkError::HecNoData(response)
+            | SplunkError::HecInvalidDataFormat(response)
+            | SplunkError::HecIncorrectIndex(response)
+            | SplunkError::HecInternalError(response)
+            | SplunkError::HecServerBusy(response)
+            | SplunkError::HecDataChannelMissing(response)
+            | SplunkError::HecInvalidDataChannel(response)
+            | SplunkError::HecEventFieldRequired(response)
+            | SplunkError::HecEventFieldBlank(response)
+            | SplunkError::HecAckDisabled(response)
+            | SplunkError::HecIndexedFieldsError(response)
+            | SplunkError::HecQueryStringAuthDisabled(response)
+            | SplunkError::HecUnhealthy(response)
+            | SplunkError::HecRequestFailed { response, .. } => Some(response),
+            _ => None,
+        }
+    }
 }
 
 impl From<serde_json::Error> for SplunkError {
@@ -33,6 +188,12 @@ impl From<serde_json::Error> for SplunkError {
     }
 }
 
+impl From<std::io::Error> for SplunkError {
+    fn from(value: std::io::Error) -> Self {
+        SplunkError::Generic(format!("IO Error: {}", value))
+    }
+}
+
 impl From<String> for SplunkError {
     fn from(value: String) -> Self {
         SplunkError::Generic(value)
@@ -41,7 +202,10 @@ impl From<String> for SplunkError {
 
 impl From<reqwest::Error> for SplunkError {
     fn from(value: reqwest::Error) -> Self {
-        SplunkError::ReqwestError(value)
+        match value.is_timeout() {
+            true => SplunkError::Timeout(value),
+            false => SplunkError::ReqwestError(value),
+        }
     }
 }
 
@@ -51,6 +215,12 @@ impl From<serde_xml_rs::Error> for SplunkError {
     }
 }
 
+impl From<toml::de::Error> for SplunkError {
+    fn from(value: toml::de::Error) -> Self {
+        SplunkError::Generic(format!("TOML Parse Error: {}", value))
+    }
+}
+
 impl From<InvalidHeaderValue> for SplunkError {
     fn from(value: InvalidHeaderValue) -> Self {
         SplunkError::Generic(format!("Invalid Header Value: {}", value))
@@ -0,0 +1,130 @@
+//! Indexer acknowledgement, so you know your events have been durably indexed
+//!
+//! Based on <https://docs.splunk.com/Documentation/Splunk/latest/Data/AboutHECIDXAck>
+//!
+//! ```no_run
+//! use splunk::hec::{AckOptions, HecClient};
+//! # async fn example() -> Result<(), splunk::errors::SplunkError> {
+//! let client = HecClient::new("token", "localhost").with_random_channel();
+//! let ack_id = client
+//!     .send_events_acknowledged(vec!["hello world"], &AckOptions::default())
+//!     .await?;
+//! println!("batch {ack_id} was indexed");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+use super::{HecClient, HecResponse, IntoHecEvent, CONTENT_TYPE_JSON};
+use crate::errors::SplunkError;
+
+/// The header used to send the channel GUID to HEC
+pub const HEC_CHANNEL_HEADER: &str = "X-Splunk-Request-Channel";
+
+#[derive(Clone, Debug)]
+/// How long to wait for acknowledgement, and how often to check
+pub struct AckOptions {
+    /// Give up waiting after this long, defaults to 5 minutes (the server forgets unqueried acks after `max_idle_time`)
+    pub timeout: Duration,
+    /// How often to poll the ack endpoint, defaults to 1 second
+    pub poll_interval: Duration,
+}
+
+impl Default for AckOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(300),
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AckRequest<'a> {
+    acks: &'a [u64],
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct AckResponse {
+    #[serde(default)]
+    acks: HashMap<String, bool>,
+}
+
+impl HecClient {
+    /// Send a batch of events and return the `ackId` the server assigned to it, without waiting for acknowledgement.
+    ///
+    /// Needs a channel, see [HecClient::with_channel]. The batch is sent as a single request, so it has to fit within
+    /// [HecClient::with_max_payload_bytes].
+    pub async fn send_events_with_ack(
+        &self,
+        events: Vec<impl IntoHecEvent>,
+    ) -> Result<u64, SplunkError> {
+        if self.channel.is_none() {
+            return Err(SplunkError::HecAckUnavailable(
+                "a channel is required for indexer acknowledgement".to_string(),
+            ));
+        }
+        let payload = self.encode_events(events)?.join("\n");
+        if payload.len() > self.max_payload_bytes {
+            return Err(SplunkError::PayloadTooLarge {
+                size: payload.len(),
+                limit: self.max_payload_bytes,
+            });
+        }
+        let response: HecResponse = self
+            .post_to_collector("/services/collector", CONTENT_TYPE_JSON, payload)
+            .await?;
+        response.ack_id()
+    }
+
+    /// Check the status of some `ackId`s on this client's channel, returns which ones have been indexed
+    pub async fn query_acks(&self, ack_ids: &[u64]) -> Result<HashMap<u64, bool>, SplunkError> {
+        let body = serde_json::to_string(&AckRequest { acks: ack_ids })?;
+        let response: AckResponse = self
+            .post_to_collector("/services/collector/ack", CONTENT_TYPE_JSON, body)
+            .await?;
+
+        let mut result = HashMap::new();
+        for (ack_id, indexed) in response.acks {
+            result.insert(ack_id.parse::<u64>()?, indexed);
+        }
+        Ok(result)
+    }
+
+    /// Poll the ack endpoint until the `ackId` has been indexed, or return [SplunkError::HecAckTimeout]
+    pub async fn wait_for_ack(
+        &self,
+        ack_id: u64,
+        ack_options: &AckOptions,
+    ) -> Result<(), SplunkError> {
+        let deadline = Instant::now() + ack_options.timeout;
+        loop {
+            let acks = self.query_acks(&[ack_id]).await?;
+            if acks.get(&ack_id).copied().unwrap_or(false) {
+                debug!("ackId {} has been indexed", ack_id);
+                return Ok(());
+            }
+            if Instant::now() + ack_options.poll_interval > deadline {
+                return Err(SplunkError::HecAckTimeout(ack_id));
+            }
+            tokio::time::sleep(ack_options.poll_interval).await;
+        }
+    }
+
+    /// Send a batch of events and wait until the indexers acknowledge it, returning the `ackId`
+    pub async fn send_events_acknowledged(
+        &self,
+        events: Vec<impl IntoHecEvent>,
+        ack_options: &AckOptions,
+    ) -> Result<u64, SplunkError> {
+        let ack_id = self.send_events_with_ack(events).await?;
+        self.wait_for_ack(ack_id, ack_options).await?;
+        Ok(ack_id)
+    }
+}
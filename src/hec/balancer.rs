@@ -0,0 +1,419 @@
+//! Spreading events across several HEC endpoints, for when there's no load balancer in front of them
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::{debug, warn};
+use tokio::time::Instant;
+
+use super::{HecClient, HecEvent, HecResponse, IntoHecEvent};
+use crate::errors::SplunkError;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// How a [HecBalancer] picks a target for each request
+pub enum BalanceStrategy {
+    /// Take turns
+    #[default]
+    RoundRobin,
+    /// Pick the target with the fewest requests in flight
+    LeastOutstanding,
+    /// Always use the same target for the balancer's channel, so `ackId`s can be checked on the server that issued
+    /// them - failing over only when it's ejected. Without a channel (see [HecBalancer::with_channel]) this takes turns
+    /// like [BalanceStrategy::RoundRobin].
+    StickyByChannel,
+}
+
+#[derive(Clone, Debug)]
+/// Configuration for a [HecBalancer]
+pub struct BalancerConfig {
+    /// How to pick a target
+    pub strategy: BalanceStrategy,
+    /// How long a target is ejected for after its first failure, defaults to 5 seconds
+    pub initial_backoff: Duration,
+    /// The longest a target is ejected for, defaults to 5 minutes
+    pub max_backoff: Duration,
+}
+
+impl Default for BalancerConfig {
+    fn default() -> Self {
+        Self {
+            strategy: BalanceStrategy::default(),
+            initial_backoff: Duration::from_secs(5),
+            max_backoff: Duration::from_secs(300),
+        }
+    }
+}
+
+impl BalancerConfig {
+    /// Set how targets are picked
+    pub fn with_strategy(mut self, strategy: BalanceStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Set how long a target is ejected for after its first failure
+    pub fn with_initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Set the longest a target is ejected for
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+struct TargetState {
+    /// Failures in a row
+    failures: u32,
+    /// Don't use the target until this time
+    ejected_until: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct Target {
+    client: HecClient,
+    outstanding: AtomicUsize,
+    state: Mutex<TargetState>,
+}
+
+impl Target {
+    fn state(&self) -> std::sync::MutexGuard<'_, TargetState> {
+        // the state's always left consistent, so a panic elsewhere doesn't matter
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Counts the request as in flight until it's dropped
+struct Outstanding<'a>(&'a AtomicUsize);
+
+impl<'a> Outstanding<'a> {
+    fn new(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter)
+    }
+}
+
+impl Drop for Outstanding<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// How a target's doing, from [HecBalancer::targets]
+pub struct TargetStatus {
+    /// The target's hostname
+    pub hostname: String,
+    /// The target's port
+    pub port: u16,
+    /// Is it currently ejected?
+    pub ejected: bool,
+    /// Failures in a row
+    pub failures: u32,
+    /// Requests in flight
+    pub outstanding: usize,
+}
+
+/// Sends events to several HEC endpoints, for when there's no load balancer in front of them
+///
+/// Each target is a [HecClient], so they can have their own tokens, TLS settings and defaults. A target that fails
+/// (it can't be reached, times out, is busy or unhealthy, or rejects its token) is ejected for a backoff period, which
+/// doubles each time it fails again. Once its backoff's up, it has to pass a health check before it's used again. If
+/// every target's still in its backoff the events are sent to them anyway, soonest-to-recover first, rather than
+/// failing without trying.
+///
+/// A failed request is retried on the next target, so a batch may be indexed more than once if a target failed after
+/// accepting part of it.
+///
+/// ```no_run
+/// use splunk::hec::{BalanceStrategy, BalancerConfig, HecBalancer, HecClient};
+/// # async fn example() -> Result<(), splunk::errors::SplunkError> {
+/// let balancer = HecBalancer::new(
+///     vec![
+///         HecClient::new("token", "hf01.example.com"),
+///         HecClient::new("token", "hf02.example.com"),
+///     ],
+///     BalancerConfig::default().with_strategy(BalanceStrategy::LeastOutstanding),
+/// )?;
+/// balancer.send_events(vec!["hello", "world"]).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct HecBalancer {
+    targets: Vec<Target>,
+    config: BalancerConfig,
+    next: AtomicUsize,
+    channel: Option<String>,
+}
+
+impl HecBalancer {
+    /// Balance across these clients, returns [SplunkError::NoHecTargets] if there aren't any
+    pub fn new(clients: Vec<HecClient>, config: BalancerConfig) -> Result<Self, SplunkError> {
+        if clients.is_empty() {
+            return Err(SplunkError::NoHecTargets);
+        }
+        let targets = clients
+            .into_iter()
+            .map(|client| Target {
+                client,
+                outstanding: AtomicUsize::new(0),
+                state: Mutex::new(TargetState::default()),
+            })
+            .collect();
+        Ok(Self {
+            targets,
+            config,
+            next: AtomicUsize::new(0),
+            channel: None,
+        })
+    }
+
+    /// Use this channel on every target, which also picks the target for [BalanceStrategy::StickyByChannel]
+    pub fn with_channel(mut self, channel: &str) -> Self {
+        self.targets = self
+            .targets
+            .into_iter()
+            .map(|target| Target {
+                client: target.client.with_channel(channel),
+                ..target
+            })
+            .collect();
+        self.channel = Some(channel.to_string());
+        self
+    }
+
+    /// How each of the targets is doing, in the order they were given
+    pub fn targets(&self) -> Vec<TargetStatus> {
+        let now = Instant::now();
+        self.targets
+            .iter()
+            .map(|target| {
+                let state = target.state();
+                TargetStatus {
+                    hostname: target.client.serverconfig.hostname.clone(),
+                    port: target.client.serverconfig.port,
+                    ejected: state.ejected_until.is_some_and(|until| until > now),
+                    failures: state.failures,
+                    outstanding: target.outstanding.load(Ordering::SeqCst),
+                }
+            })
+            .collect()
+    }
+
+    /// Check the health endpoint of every target, ejecting the unhealthy ones and reinstating the healthy ones
+    pub async fn check_health(&self) {
+        for target in self.targets.iter() {
+            self.check_target(target).await;
+        }
+    }
+
+    /// Send an event, either something [serde::Serialize] or a [HecEvent]
+    pub async fn send_event(&self, event: impl IntoHecEvent) -> Result<HecResponse, SplunkError> {
+        let mut responses = self.send_events(vec![event]).await?;
+        responses
+            .pop()
+            .ok_or_else(|| SplunkError::Generic("No response from HEC".to_string()))
+    }
+
+    /// Send events to one of the targets, trying the others if it fails.
+    ///
+    /// Only a failure of the target itself ejects it and moves on to the next one. Anything else, like an event HEC
+    /// won't accept ([SplunkError::HecInvalidDataFormat], [SplunkError::HecIncorrectIndex]) or an acknowledgement that
+    /// didn't arrive in time, is returned straight away - another target would say the same thing, or might index the
+    /// events twice.
+    ///
+    /// If a target accepted some of the requests before failing, only the rest are sent to the next one. The error's
+    /// [SplunkError::HecPartiallySent] if anything was accepted before giving up.
+    pub async fn send_events(
+        &self,
+        events: Vec<impl IntoHecEvent>,
+    ) -> Result<Vec<HecResponse>, SplunkError> {
+        let mut events = events
+            .into_iter()
+            .map(IntoHecEvent::into_hec_event)
+            .collect::<Result<Vec<HecEvent>, SplunkError>>()?;
+
+        let mut accepted: Vec<HecResponse> = Vec::new();
+        let mut sent: usize = 0;
+        let mut last_error = SplunkError::NoHecTargets;
+        // whether we've tried a target that wasn't in its backoff
+        let mut tried_available = false;
+        for index in self.candidates() {
+            let Some(target) = self.targets.get(index) else {
+                continue;
+            };
+            let backing_off = target
+                .state()
+                .ejected_until
+                .is_some_and(|until| until > Instant::now());
+            match backing_off {
+                // the ejected ones come last, and they're only a last resort
+                true if tried_available => break,
+                true => {}
+                false if !self.ready_to_use(target).await => continue,
+                false => tried_available = true,
+            }
+            let _outstanding = Outstanding::new(&target.outstanding);
+            match target.client.send_events(events.clone()).await {
+                Ok(responses) => {
+                    self.reinstate(target);
+                    accepted.extend(responses);
+                    return Ok(accepted);
+                }
+                Err(err) => {
+                    // don't send what this target's already accepted again
+                    let err = match err {
+                        SplunkError::HecPartiallySent {
+                            responses,
+                            sent: partial,
+                            error,
+                        } => {
+                            accepted.extend(responses);
+                            events.drain(..partial.min(events.len()));
+                            sent += partial;
+                            *error
+                        }
+                        err => err,
+                    };
+                    if !is_target_failure(&err) {
+                        return Err(SplunkError::partially_sent(accepted, sent, err));
+                    }
+                    warn!(
+                        "HEC target {} failed, trying the next one: {:?}",
+                        target.client.serverconfig.hostname, err
+                    );
+                    self.eject(target);
+                    last_error = err;
+                }
+            }
+        }
+        Err(SplunkError::partially_sent(accepted, sent, last_error))
+    }
+
+    /// The order to try the targets in - available ones by strategy, then ejected ones soonest-to-recover first
+    pub(crate) fn candidates(&self) -> Vec<usize> {
+        let count = self.targets.len();
+        let start = match (self.config.strategy, &self.channel) {
+            (BalanceStrategy::StickyByChannel, Some(channel)) => {
+                let mut hasher = DefaultHasher::new();
+                channel.hash(&mut hasher);
+                (hasher.finish() % count as u64) as usize
+            }
+            // with no channel to stick to, take turns
+            _ => self.next.fetch_add(1, Ordering::Relaxed) % count,
+        };
+        let order = (0..count).map(|offset| (start + offset) % count);
+
+        let now = Instant::now();
+        let mut available: Vec<usize> = Vec::new();
+        let mut ejected: Vec<(Instant, usize)> = Vec::new();
+        for index in order {
+            let Some(target) = self.targets.get(index) else {
+                continue;
+            };
+            match target.state().ejected_until {
+                Some(until) if until > now => ejected.push((until, index)),
+                _ => available.push(index),
+            }
+        }
+        if self.config.strategy == BalanceStrategy::LeastOutstanding {
+            // stable, so ties are still taken in turn
+            available.sort_by_key(|index| {
+                self.targets
+                    .get(*index)
+                    .map(|target| target.outstanding.load(Ordering::SeqCst))
+                    .unwrap_or(usize::MAX)
+            });
+        }
+        ejected.sort_by_key(|(until, _)| *until);
+        available
+            .into_iter()
+            .chain(ejected.into_iter().map(|(_, index)| index))
+            .collect()
+    }
+
+    /// A target that's been ejected has to pass a health check once its backoff's up, before it's used again
+    async fn ready_to_use(&self, target: &Target) -> bool {
+        let ejected = target.state().failures > 0;
+        match ejected {
+            true => self.check_target(target).await,
+            false => true,
+        }
+    }
+
+    /// Check a target's health and update its state, returning whether it's healthy
+    async fn check_target(&self, target: &Target) -> bool {
+        match target.client.check_health().await {
+            Ok(health) if health.is_healthy() => {
+                self.reinstate(target);
+                true
+            }
+            Ok(health) => {
+                warn!(
+                    "HEC target {} is unhealthy: {:?}",
+                    target.client.serverconfig.hostname, health
+                );
+                self.eject(target);
+                false
+            }
+            Err(err) => {
+                warn!(
+                    "HEC target {} health check failed: {:?}",
+                    target.client.serverconfig.hostname, err
+                );
+                self.eject(target);
+                false
+            }
+        }
+    }
+
+    /// Take a target out of rotation, for longer each time it fails
+    fn eject(&self, target: &Target) {
+        let mut state = target.state();
+        state.failures = state.failures.saturating_add(1);
+        let backoff = self
+            .config
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(state.failures - 1))
+            .min(self.config.max_backoff);
+        debug!(
+            "Ejecting HEC target {} for {:?}",
+            target.client.serverconfig.hostname, backoff
+        );
+        state.ejected_until = Some(Instant::now() + backoff);
+    }
+
+    /// Put a target back in rotation
+    fn reinstate(&self, target: &Target) {
+        let mut state = target.state();
+        if state.failures > 0 {
+            debug!(
+                "Reinstating HEC target {}",
+                target.client.serverconfig.hostname
+            );
+        }
+        *state = TargetState::default();
+    }
+}
+
+/// Is this the target's fault, rather than the events'? Anything worth retrying is, apart from an acknowledgement that
+/// didn't arrive, since the events might have been indexed. Each target has its own token, so a bad one counts too.
+fn is_target_failure(error: &SplunkError) -> bool {
+    match error {
+        SplunkError::HecAckTimeout(_) => false,
+        SplunkError::HecTokenDisabled(_)
+        | SplunkError::HecTokenRequired(_)
+        | SplunkError::HecInvalidAuthorization(_)
+        | SplunkError::HecInvalidToken(_) => true,
+        error => error.is_retriable(),
+    }
+}
@@ -0,0 +1,143 @@
+//! Splitting events into requests which fit within the server's `max_content_length`
+//!
+//! HEC rejects a request body larger than `max_content_length` (in the `[http]` stanza of `limits.conf`) with a 413,
+//! which drops the whole batch. Batches are split so each request stays under [HecClient::with_max_payload_bytes],
+//! and a single event that's too big on its own is handled based on the [OversizedEventPolicy].
+
+use serde_json::Value;
+
+use super::{HecClient, HecEvent};
+use crate::errors::SplunkError;
+
+/// The default maximum request body size, in bytes
+pub const DEFAULT_MAX_PAYLOAD_BYTES: usize = 1_000_000;
+
+/// The default maximum number of events in a single request
+pub const DEFAULT_MAX_BATCH_EVENTS: usize = 1000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// What to do with a single event that's bigger than the maximum payload size
+pub enum OversizedEventPolicy {
+    /// Return [SplunkError::PayloadTooLarge] and drop the event
+    #[default]
+    Reject,
+    /// Cut the end off string events (and raw lines) so they fit, anything else is rejected
+    Truncate,
+}
+
+impl HecClient {
+    /// Set the maximum request body size in bytes, this should match the server's `max_content_length`
+    pub fn with_max_payload_bytes(mut self, max_payload_bytes: usize) -> Self {
+        self.max_payload_bytes = max_payload_bytes.max(1);
+        self
+    }
+
+    /// Set the maximum number of events sent in a single request, used when [HecClient::flush] isn't given a batch size
+    pub fn with_max_batch_events(mut self, max_batch_events: usize) -> Self {
+        self.max_batch_events = max_batch_events.max(1);
+        self
+    }
+
+    /// Set what happens to an event that's too big to send on its own
+    pub fn with_oversized_event_policy(mut self, policy: OversizedEventPolicy) -> Self {
+        self.oversized_event_policy = policy;
+        self
+    }
+
+    /// Serialize an event for sending, applying the [OversizedEventPolicy] if it's too big
+    pub(crate) fn encode_event(&self, event: &HecEvent) -> Result<String, SplunkError> {
+        let encoded = event.to_json(self)?;
+        if encoded.len() <= self.max_payload_bytes {
+            return Ok(encoded);
+        }
+        let too_large = SplunkError::PayloadTooLarge {
+            size: encoded.len(),
+            limit: self.max_payload_bytes,
+        };
+        match (&self.oversized_event_policy, &event.event) {
+            (OversizedEventPolicy::Truncate, Value::String(data)) => {
+                // escaping means every byte removed from the string shortens the JSON by at least a byte
+                let overflow = encoded.len() - self.max_payload_bytes;
+                let Some(truncated) = truncate(data, data.len().saturating_sub(overflow)) else {
+                    return Err(too_large);
+                };
+                let truncated = HecEvent {
+                    event: Value::String(truncated.to_string()),
+                    ..event.clone()
+                };
+                truncated.to_json(self)
+            }
+            _ => Err(too_large),
+        }
+    }
+
+    /// Check a line for the raw endpoint fits, applying the [OversizedEventPolicy] if it doesn't
+    pub(crate) fn encode_raw_line<'a>(&self, line: &'a str) -> Result<&'a str, SplunkError> {
+        if line.len() <= self.max_payload_bytes {
+            return Ok(line);
+        }
+        match self.oversized_event_policy {
+            OversizedEventPolicy::Truncate => truncate(line, self.max_payload_bytes),
+            OversizedEventPolicy::Reject => None,
+        }
+        .ok_or(SplunkError::PayloadTooLarge {
+            size: line.len(),
+            limit: self.max_payload_bytes,
+        })
+    }
+
+    /// Join encoded events into newline-separated payloads, each within the size and count limits, along with the
+    /// number of events in each
+    pub(crate) fn split_batches(
+        &self,
+        encoded: impl IntoIterator<Item = impl AsRef<str>>,
+        max_events: usize,
+    ) -> Vec<(String, usize)> {
+        self.split_tagged_batches(encoded.into_iter().map(|event| (event, ())), max_events)
+            .into_iter()
+            .map(|(payload, events)| (payload, events.len()))
+            .collect()
+    }
+
+    /// Like [HecClient::split_batches], keeping track of which items went into each payload so a failed request can
+    /// be retried on its own
+    pub(crate) fn split_tagged_batches<T>(
+        &self,
+        encoded: impl IntoIterator<Item = (impl AsRef<str>, T)>,
+        max_events: usize,
+    ) -> Vec<(String, Vec<T>)> {
+        let mut payloads: Vec<(String, Vec<T>)> = Vec::new();
+        let mut payload = String::new();
+        let mut items: Vec<T> = Vec::new();
+        for (event, item) in encoded {
+            let event = event.as_ref();
+            if !items.is_empty()
+                && (items.len() >= max_events
+                    || payload.len() + 1 + event.len() > self.max_payload_bytes)
+            {
+                payloads.push((std::mem::take(&mut payload), std::mem::take(&mut items)));
+            }
+            if !items.is_empty() {
+                payload.push('\n');
+            }
+            payload.push_str(event);
+            items.push(item);
+        }
+        if !items.is_empty() {
+            payloads.push((payload, items));
+        }
+        payloads
+    }
+}
+
+/// Cut a string down to at most `max_bytes`, on a character boundary - `None` if there'd be nothing left
+fn truncate(data: &str, max_bytes: usize) -> Option<&str> {
+    let mut end = max_bytes.min(data.len());
+    while !data.is_char_boundary(end) {
+        end -= 1;
+    }
+    match end {
+        0 => None,
+        _ => Some(&data[..end]),
+    }
+}
@@ -0,0 +1,165 @@
+//! Events with per-event metadata - timestamps, host, indexed fields and index/source/sourcetype overrides
+//!
+//! Based on <https://docs.splunk.com/Documentation/Splunk/latest/Data/FormateventsforHTTPEventCollector>
+//!
+//! ```
+//! use std::time::SystemTime;
+//! use serde_json::json;
+//! use splunk::hec::HecEvent;
+//!
+//! let event = HecEvent::new(json!({"message": "hello world"}))
+//!     .with_time(SystemTime::now())
+//!     .with_host("web01")
+//!     .with_sourcetype("my:app")
+//!     .with_field("environment", "production");
+//! ```
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::HecClient;
+use crate::errors::SplunkError;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+/// A single event, along with the metadata HEC will apply to it.
+///
+/// Anything left as `None` falls back to the [HecClient]'s setting, then to the server's default.
+pub struct HecEvent {
+    /// The event data
+    pub event: Value,
+    /// Event time in seconds since the epoch, with subsecond precision
+    pub time: Option<f64>,
+    /// The host field
+    pub host: Option<String>,
+    /// The target index
+    pub index: Option<String>,
+    /// The source field
+    pub source: Option<String>,
+    /// The sourcetype field
+    pub sourcetype: Option<String>,
+    /// Indexed fields, which can be searched quickly with `tstats`
+    pub fields: Map<String, Value>,
+}
+
+/// The JSON envelope HEC expects
+#[derive(Serialize)]
+struct HecEventPayload<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    index: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sourcetype: Option<&'a str>,
+    event: &'a Value,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    fields: &'a Map<String, Value>,
+}
+
+impl HecEvent {
+    /// Create an event with no metadata set
+    pub fn new(event: impl Into<Value>) -> Self {
+        Self {
+            event: event.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Create an event from anything that can be serialized
+    pub fn from_serializable(event: impl Serialize) -> Result<Self, SplunkError> {
+        Ok(Self::new(serde_json::to_value(event)?))
+    }
+
+    /// Set the event time
+    pub fn with_time(mut self, time: SystemTime) -> Self {
+        self.time = Some(epoch_seconds(time));
+        self
+    }
+
+    /// Set the event time as seconds since the epoch
+    pub fn with_epoch_time(mut self, time: f64) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    /// Set the host field
+    pub fn with_host(mut self, host: &str) -> Self {
+        self.host = Some(host.to_string());
+        self
+    }
+
+    /// Set the target index
+    pub fn with_index(mut self, index: &str) -> Self {
+        self.index = Some(index.to_string());
+        self
+    }
+
+    /// Set the source field
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = Some(source.to_string());
+        self
+    }
+
+    /// Set the sourcetype field
+    pub fn with_sourcetype(mut self, sourcetype: &str) -> Self {
+        self.sourcetype = Some(sourcetype.to_string());
+        self
+    }
+
+    /// Add an indexed field
+    pub fn with_field(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.fields.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Serialize the event for sending, filling in any metadata that isn't set from the client
+    pub(crate) fn to_json(&self, client: &HecClient) -> Result<String, SplunkError> {
+        let payload = HecEventPayload {
+            time: self.time,
+            host: self.host.as_deref().or(client.host.as_deref()),
+            index: self.index.as_deref().or(client.index.as_deref()),
+            source: self.source.as_deref().or(client.source.as_deref()),
+            sourcetype: self.sourcetype.as_deref().or(client.sourcetype.as_deref()),
+            event: &self.event,
+            fields: &self.fields,
+        };
+        serde_json::to_string(&payload).map_err(SplunkError::from)
+    }
+}
+
+/// Convert a [SystemTime] to seconds since the epoch, the way HEC wants it
+pub(crate) fn epoch_seconds(time: SystemTime) -> f64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs_f64(),
+        Err(err) => -err.duration().as_secs_f64(),
+    }
+}
+
+/// Things which can be sent to HEC as events.
+///
+/// Anything that implements [Serialize] is sent as the `event` field with no extra metadata,
+/// use a [HecEvent] if you want to set the time, host, indexed fields and so on.
+///
+/// Because of that blanket impl, a type with its own impl (like [HecEvent] and [super::MetricEvent]) can't also
+/// implement [Serialize] - the two impls would overlap and the crate wouldn't compile.
+pub trait IntoHecEvent {
+    /// Convert into a [HecEvent]
+    fn into_hec_event(self) -> Result<HecEvent, SplunkError>;
+}
+
+impl<T: Serialize> IntoHecEvent for T {
+    fn into_hec_event(self) -> Result<HecEvent, SplunkError> {
+        HecEvent::from_serializable(self)
+    }
+}
+
+impl IntoHecEvent for HecEvent {
+    fn into_hec_event(self) -> Result<HecEvent, SplunkError> {
+        Ok(self)
+    }
+}
@@ -0,0 +1,201 @@
+//! Flushing the queue, one batch at a time, without losing events when a batch fails
+
+use log::{debug, error};
+
+use super::{HecClient, HecEvent, HecResponse};
+use crate::errors::SplunkError;
+
+#[derive(Debug)]
+/// Why a batch failed
+pub struct BatchError {
+    /// The number of events in the batch
+    pub events: usize,
+    /// What went wrong
+    pub error: SplunkError,
+    /// For HEC error code 6, the position of the invalid event in the batch
+    pub invalid_event_number: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+/// What happened during a [HecClient::flush]
+pub struct FlushReport {
+    /// Events accepted by the server
+    pub sent: usize,
+    /// Events which weren't sent - the requeued and dead-lettered events together
+    pub failed: usize,
+    /// Events from failed batches which are still queued, to try again on the next flush
+    pub requeued: usize,
+    /// Events which can't be sent, and have been taken off the queue
+    pub dead_lettered: Vec<HecEvent>,
+    /// Each batch that failed
+    pub errors: Vec<BatchError>,
+}
+
+impl FlushReport {
+    /// Did everything get sent?
+    pub fn is_success(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// What to do with the events in a failed batch
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Disposition {
+    /// Leave them queued and stop flushing
+    Requeue,
+    /// Take them all off the queue
+    DeadLetter,
+    /// The events before this one were sent, this one's dead-lettered and the rest stay queued
+    InvalidEvent(usize),
+}
+
+impl Disposition {
+    /// Anything [SplunkError::is_retriable] is requeued, everything else would fail the same way next time
+    pub(crate) fn from_error(error: &SplunkError, batch_len: usize) -> Self {
+        match error {
+            SplunkError::HecInvalidDataFormat(HecResponse {
+                invalid_event_number: Some(number),
+                ..
+            }) if *number < batch_len => Self::InvalidEvent(*number),
+            error if error.is_retriable() => Self::Requeue,
+            _ => Self::DeadLetter,
+        }
+    }
+}
+
+impl HecClient {
+    /// Flush the queue out to HEC, in batches of `batch_size` events (defaults to [HecClient::with_max_batch_events])
+    /// which are kept within [HecClient::with_max_payload_bytes].
+    ///
+    /// Events are only taken off the queue once the batch they're in has been accepted. When a batch fails, what happens
+    /// depends on the error:
+    ///
+    /// - HEC error code 6 with an `invalid-event-number` - the events before it were indexed, the invalid event is
+    ///   dead-lettered and the rest stay queued.
+    /// - Errors worth retrying (see [SplunkError::is_retriable] - connection problems, timeouts, busy or unhealthy
+    ///   servers) - the batch stays queued and the flush stops, so it's retried next time.
+    /// - Anything else (bad data, an index the token can't write to, invalid or disabled tokens, a 413) - the batch is
+    ///   dead-lettered, sending it again would fail the same way.
+    ///
+    /// Dead-lettered events are handed back in the [FlushReport]. If anything wasn't sent this returns [SplunkError::FlushFailed] with the [FlushReport].
+    ///
+    /// An event too big to send is dead-lettered as [SplunkError::PayloadTooLarge], unless the
+    /// [super::OversizedEventPolicy] lets it be truncated.
+    pub async fn flush(&mut self, batch_size: Option<u32>) -> Result<FlushReport, SplunkError> {
+        let batch_size = batch_size
+            .map(|size| size as usize)
+            .unwrap_or(self.max_batch_events)
+            .max(1);
+
+        let mut report = FlushReport::default();
+        loop {
+            self.refill_from_spool(batch_size).await?;
+            let mut batch: Vec<String> = Vec::new();
+            let mut batch_bytes: usize = 0;
+            let mut oversized: Option<SplunkError> = None;
+            {
+                let queue = self.queue.read().await;
+                for event in queue.iter().take(batch_size) {
+                    let encoded = match self.encode_event(event) {
+                        Ok(encoded) => encoded,
+                        // send what we've got, the oversized event will be dealt with next time around
+                        Err(_) if !batch.is_empty() => break,
+                        Err(err) => {
+                            oversized = Some(err);
+                            break;
+                        }
+                    };
+                    if !batch.is_empty() && batch_bytes + encoded.len() > self.max_payload_bytes {
+                        break;
+                    }
+                    batch_bytes += encoded.len() + 1;
+                    batch.push(encoded);
+                }
+            }
+
+            if let Some(err) = oversized {
+                self.batch_failed(&mut report, 1, err).await?;
+                continue;
+            }
+            if batch.is_empty() {
+                break;
+            }
+
+            let batch_len = batch.len();
+            match self.send_payload(batch.join("\n")).await {
+                Ok(_) => {
+                    self.take_from_queue(batch_len).await;
+                    self.commit_spool(batch_len).await?;
+                    report.sent += batch_len;
+                }
+                Err(err) => {
+                    if !self.batch_failed(&mut report, batch_len, err).await? {
+                        break;
+                    }
+                }
+            }
+        }
+        debug!(
+            "Flush sent {} events, {} failed",
+            report.sent, report.failed
+        );
+
+        match report.is_success() {
+            true => Ok(report),
+            false => Err(SplunkError::FlushFailed(Box::new(report))),
+        }
+    }
+
+    /// Deal with a batch of `batch_len` events at the front of the queue that failed, returning false if the flush
+    /// should stop
+    async fn batch_failed(
+        &mut self,
+        report: &mut FlushReport,
+        batch_len: usize,
+        err: SplunkError,
+    ) -> Result<bool, SplunkError> {
+        error!("Failed to send a batch of {} events: {:?}", batch_len, err);
+        let disposition = Disposition::from_error(&err, batch_len);
+        let invalid_event_number = err
+            .hec_response()
+            .and_then(|response| response.invalid_event_number);
+        report.errors.push(BatchError {
+            events: batch_len,
+            error: err,
+            invalid_event_number,
+        });
+
+        let keep_going = match disposition {
+            Disposition::Requeue => {
+                report.requeued += batch_len;
+                report.failed += batch_len;
+                false
+            }
+            Disposition::DeadLetter => {
+                let events = self.take_from_queue(batch_len).await;
+                self.commit_spool(batch_len).await?;
+                report.failed += events.len();
+                report.dead_lettered.extend(events);
+                true
+            }
+            Disposition::InvalidEvent(number) => {
+                // the server stops at the invalid event, so everything before it was indexed
+                self.take_from_queue(number).await;
+                let events = self.take_from_queue(1).await;
+                self.commit_spool(number + 1).await?;
+                report.sent += number;
+                report.failed += events.len();
+                report.dead_lettered.extend(events);
+                true
+            }
+        };
+        Ok(keep_going)
+    }
+
+    /// Remove events from the front of the queue
+    async fn take_from_queue(&self, count: usize) -> Vec<HecEvent> {
+        let mut queue = self.queue.write().await;
+        let count = count.min(queue.len());
+        queue.drain(..count).collect()
+    }
+}
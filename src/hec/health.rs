@@ -0,0 +1,113 @@
+//! HEC health checks
+//!
+//! Based on <https://docs.splunk.com/Documentation/Splunk/latest/RESTREF/RESTinput#services.2Fcollector.2Fhealth>
+//!
+//! ```no_run
+//! use splunk::hec::{HecClient, HecHealth};
+//! # async fn example() -> Result<(), splunk::errors::SplunkError> {
+//! let client = HecClient::new("token", "localhost");
+//! match client.check_health().await? {
+//!     HecHealth::Healthy => println!("ready to go"),
+//!     other => println!("not yet: {other:?}"),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use super::HecClient;
+use crate::errors::SplunkError;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+/// Deserializer for the response from HEC Health Checks
+pub struct HecHealthResult {
+    /// The message from the server, eg `HEC is healthy`
+    #[serde(default)]
+    pub text: String,
+    /// The HEC status code, `17` when everything's fine
+    #[serde(default)]
+    pub code: u32,
+}
+
+impl HecHealthResult {
+    /// What the response means
+    pub fn health(&self) -> HecHealth {
+        match self.code {
+            17 => HecHealth::Healthy,
+            18 => HecHealth::QueuesFull,
+            19 => HecHealth::AckUnavailable,
+            20 => HecHealth::QueuesFullAckUnavailable,
+            2..=4 => HecHealth::InvalidToken,
+            1 => HecHealth::Disabled,
+            _ => HecHealth::Unknown(self.clone()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// The state of a HEC endpoint, from [HecClient::check_health]
+pub enum HecHealth {
+    /// Code 17, ready to accept events
+    Healthy,
+    /// Code 18, the server's queues are full and it's not taking events
+    QueuesFull,
+    /// Code 19, the indexer acknowledgement service isn't available
+    AckUnavailable,
+    /// Code 20, the queues are full and the indexer acknowledgement service isn't available
+    QueuesFullAckUnavailable,
+    /// The token's missing or invalid
+    InvalidToken,
+    /// The token is disabled
+    Disabled,
+    /// Something we didn't expect
+    Unknown(HecHealthResult),
+}
+
+impl HecHealth {
+    /// Is it worth sending events?
+    pub fn is_healthy(&self) -> bool {
+        matches!(self, HecHealth::Healthy)
+    }
+}
+
+impl HecClient {
+    async fn do_healthcheck(&self, endpoint: &str) -> Result<HecHealthResult, SplunkError> {
+        let client = self.get_client()?;
+        let response = client
+            .get(self.collector_url(endpoint)?)
+            .timeout(self.request_timeout())
+            .headers(self.get_headers()?)
+            .send()
+            .await?;
+        // unhealthy servers answer with an error status, but the body still says why
+        let status = response.status();
+        let body = response.text().await?;
+        serde_json::from_str(&body).map_err(|err| {
+            SplunkError::Generic(format!(
+                "Couldn't parse health check response (status {status}): {err:?} {body}"
+            ))
+        })
+    }
+
+    /// Do a healthcheck and return the response
+    pub async fn get_health(&self) -> Result<HecHealthResult, SplunkError> {
+        self.do_healthcheck("/services/collector/health").await
+    }
+
+    /// The separate HEC health endpoint for ACK-related/enabled hosts
+    pub async fn get_health_ack(&self) -> Result<HecHealthResult, SplunkError> {
+        self.do_healthcheck("/services/collector/health?ack=true")
+            .await
+    }
+
+    /// Check whether HEC is ready for events, including the acknowledgement service if
+    /// [HecClient::with_indexer_ack] is on
+    pub async fn check_health(&self) -> Result<HecHealth, SplunkError> {
+        let result = match self.ack_options {
+            Some(_) => self.get_health_ack().await?,
+            None => self.get_health().await?,
+        };
+        Ok(result.health())
+    }
+}
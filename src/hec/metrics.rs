@@ -0,0 +1,453 @@
+//! Sending metrics to a metrics index through HEC
+//!
+//! Based on <https://docs.splunk.com/Documentation/Splunk/latest/Metrics/GetMetricsInOther#Get_metrics_in_from_clients_over_HTTP_or_HTTPS>
+//!
+//! A [MetricEvent] with a single measurement is sent in the single-metric format, one with several
+//! measurements is sent in the multi-metric format, which needs Splunk 8.0 or later.
+//!
+//! ```
+//! use splunk::hec::{HecClient, MetricEvent};
+//!
+//! let event = MetricEvent::new()
+//!     .with_measurement("cpu.idle", 95.2)
+//!     .with_measurement("cpu.user", 3.1)
+//!     .with_dimension("region", "us-west-1");
+//! ```
+
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+use serde_json::{Map, Value};
+use tokio::sync::Mutex;
+
+use super::event::epoch_seconds;
+use super::{HecClient, HecEvent, IntoHecEvent};
+use crate::errors::SplunkError;
+
+/// Dimension names are sorted, so the same set of dimensions is always grouped together
+type Dimensions = BTreeMap<String, String>;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+/// One or more measurements which share a timestamp and set of dimensions
+pub struct MetricEvent {
+    /// Event time in seconds since the epoch, with subsecond precision
+    pub time: Option<f64>,
+    /// The host field
+    pub host: Option<String>,
+    /// The target metrics index
+    pub index: Option<String>,
+    /// The source field
+    pub source: Option<String>,
+    /// Dimensions to attach to the measurements
+    pub dimensions: Dimensions,
+    /// Measurement names and values
+    pub measurements: BTreeMap<String, f64>,
+}
+
+impl MetricEvent {
+    /// Create an empty metric event, add measurements with [MetricEvent::with_measurement]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a measurement
+    pub fn with_measurement(mut self, name: &str, value: f64) -> Self {
+        self.measurements.insert(name.to_string(), value);
+        self
+    }
+
+    /// Add a dimension
+    pub fn with_dimension(mut self, name: &str, value: &str) -> Self {
+        self.dimensions.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Set the event time
+    pub fn with_time(mut self, time: SystemTime) -> Self {
+        self.time = Some(epoch_seconds(time));
+        self
+    }
+
+    /// Set the host field
+    pub fn with_host(mut self, host: &str) -> Self {
+        self.host = Some(host.to_string());
+        self
+    }
+
+    /// Set the target metrics index
+    pub fn with_index(mut self, index: &str) -> Self {
+        self.index = Some(index.to_string());
+        self
+    }
+
+    /// Set the source field
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = Some(source.to_string());
+        self
+    }
+
+    /// Check the event will be accepted by a metrics index
+    pub fn validate(&self) -> Result<(), SplunkError> {
+        if self.measurements.is_empty() {
+            return Err(SplunkError::InvalidMetric(
+                "a metric event needs at least one measurement".to_string(),
+            ));
+        }
+        for (name, value) in self.measurements.iter() {
+            if name.trim().is_empty() {
+                return Err(SplunkError::InvalidMetric(
+                    "metric names can't be blank".to_string(),
+                ));
+            }
+            if !value.is_finite() {
+                return Err(SplunkError::InvalidMetric(format!(
+                    "the value of {name} must be a finite number, got {value}"
+                )));
+            }
+        }
+        for name in self.dimensions.keys() {
+            if name.trim().is_empty() {
+                return Err(SplunkError::InvalidMetric(
+                    "dimension names can't be blank".to_string(),
+                ));
+            }
+            if name == "_value" || name.starts_with("metric_name") {
+                return Err(SplunkError::InvalidMetric(format!(
+                    "{name} is reserved and can't be used as a dimension"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl IntoHecEvent for MetricEvent {
+    fn into_hec_event(self) -> Result<HecEvent, SplunkError> {
+        self.validate()?;
+
+        let mut fields: Map<String, Value> = self
+            .dimensions
+            .into_iter()
+            .map(|(name, value)| (name, Value::String(value)))
+            .collect();
+
+        if self.measurements.len() == 1 {
+            for (name, value) in self.measurements {
+                fields.insert("metric_name".to_string(), Value::String(name));
+                fields.insert("_value".to_string(), value.into());
+            }
+        } else {
+            for (name, value) in self.measurements {
+                fields.insert(format!("metric_name:{name}"), value.into());
+            }
+        }
+
+        Ok(HecEvent {
+            event: Value::String("metric".to_string()),
+            time: self.time,
+            host: self.host,
+            index: self.index,
+            source: self.source,
+            sourcetype: None,
+            fields,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct RecorderState {
+    gauges: BTreeMap<Dimensions, BTreeMap<String, f64>>,
+    counters: BTreeMap<Dimensions, BTreeMap<String, f64>>,
+    events: Vec<MetricEvent>,
+}
+
+/// Where a metric event being flushed came from, so it can be put back if it isn't sent
+#[derive(Debug)]
+enum Unsent {
+    /// Built from the gauges and counters for one set of dimensions
+    Aggregated {
+        dimensions: Dimensions,
+        gauges: BTreeMap<String, f64>,
+        counters: BTreeMap<String, f64>,
+    },
+    /// Passed to [MetricsRecorder::record]
+    Recorded(MetricEvent),
+}
+
+impl RecorderState {
+    /// Is this name already recorded as the other kind of measurement with these dimensions?
+    fn recorded_as(
+        aggregates: &BTreeMap<Dimensions, BTreeMap<String, f64>>,
+        dimensions: &Dimensions,
+        name: &str,
+    ) -> bool {
+        aggregates
+            .get(dimensions)
+            .is_some_and(|measurements| measurements.contains_key(name))
+    }
+
+    /// Put back metrics that weren't sent, merging them with anything recorded since the flush started
+    fn restore(&mut self, unsent: impl IntoIterator<Item = Unsent>) {
+        let mut events: Vec<MetricEvent> = Vec::new();
+        for item in unsent {
+            match item {
+                Unsent::Aggregated {
+                    dimensions,
+                    gauges,
+                    counters,
+                } => {
+                    // anything recorded since the flush started is newer, so it wins if the kind's changed
+                    for (name, value) in gauges {
+                        if Self::recorded_as(&self.counters, &dimensions, &name) {
+                            continue;
+                        }
+                        // a gauge recorded since is newer too
+                        self.gauges
+                            .entry(dimensions.clone())
+                            .or_default()
+                            .entry(name)
+                            .or_insert(value);
+                    }
+                    for (name, value) in counters {
+                        if Self::recorded_as(&self.gauges, &dimensions, &name) {
+                            continue;
+                        }
+                        *self
+                            .counters
+                            .entry(dimensions.clone())
+                            .or_default()
+                            .entry(name)
+                            .or_default() += value;
+                    }
+                }
+                Unsent::Recorded(event) => events.push(event),
+            }
+        }
+        // the unsent events were recorded first, so they stay at the front
+        events.append(&mut self.events);
+        self.events = events;
+    }
+}
+
+/// Collects gauges, counters and metric events, then sends them to HEC in batches when you call [MetricsRecorder::flush].
+///
+/// Gauges keep the last value recorded, counters are summed and reset after each successful flush. Measurements with
+/// the same dimensions are sent together as a single multi-metric event, so a name can't be both a gauge and a counter
+/// with the same dimensions until it's been flushed.
+///
+/// ```no_run
+/// use splunk::hec::{HecClient, MetricsRecorder};
+/// # async fn example() -> Result<(), splunk::errors::SplunkError> {
+/// let recorder = MetricsRecorder::new(HecClient::new("token", "localhost").with_index("metrics"));
+/// recorder.gauge("queue.depth", 12.0, &[("queue", "ingest")]).await?;
+/// recorder.counter("requests", 1.0, &[("status", "200")]).await?;
+/// recorder.flush().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MetricsRecorder {
+    client: HecClient,
+    batch_size: usize,
+    state: Mutex<RecorderState>,
+}
+
+impl MetricsRecorder {
+    /// Create a recorder which sends using this client, in batches of up to 1000 events
+    pub fn new(client: HecClient) -> Self {
+        Self {
+            client,
+            batch_size: 1000,
+            state: Mutex::new(RecorderState::default()),
+        }
+    }
+
+    /// Set the maximum number of metric events sent in each request
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// The client used to send the metrics
+    pub fn client(&self) -> &HecClient {
+        &self.client
+    }
+
+    /// Validate a single measurement before we store it
+    fn check(
+        name: &str,
+        value: f64,
+        dimensions: &[(&str, &str)],
+    ) -> Result<Dimensions, SplunkError> {
+        let dimensions: Dimensions = dimensions
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        MetricEvent {
+            dimensions: dimensions.clone(),
+            ..Default::default()
+        }
+        .with_measurement(name, value)
+        .validate()?;
+        Ok(dimensions)
+    }
+
+    /// Set a gauge to the given value, replacing any value recorded since the last flush.
+    ///
+    /// Returns [SplunkError::InvalidMetric] if the name's waiting to be sent as a counter with these dimensions.
+    pub async fn gauge(
+        &self,
+        name: &str,
+        value: f64,
+        dimensions: &[(&str, &str)],
+    ) -> Result<(), SplunkError> {
+        let dimensions = Self::check(name, value, dimensions)?;
+        let mut state = self.state.lock().await;
+        if RecorderState::recorded_as(&state.counters, &dimensions, name) {
+            return Err(SplunkError::InvalidMetric(format!(
+                "{name} is already a counter with these dimensions"
+            )));
+        }
+        state
+            .gauges
+            .entry(dimensions)
+            .or_default()
+            .insert(name.to_string(), value);
+        Ok(())
+    }
+
+    /// Add to a counter, which is sent as the total since the last flush.
+    ///
+    /// Returns [SplunkError::InvalidMetric] if the name's waiting to be sent as a gauge with these dimensions.
+    pub async fn counter(
+        &self,
+        name: &str,
+        delta: f64,
+        dimensions: &[(&str, &str)],
+    ) -> Result<(), SplunkError> {
+        let dimensions = Self::check(name, delta, dimensions)?;
+        let mut state = self.state.lock().await;
+        if RecorderState::recorded_as(&state.gauges, &dimensions, name) {
+            return Err(SplunkError::InvalidMetric(format!(
+                "{name} is already a gauge with these dimensions"
+            )));
+        }
+        *state
+            .counters
+            .entry(dimensions)
+            .or_default()
+            .entry(name.to_string())
+            .or_default() += delta;
+        Ok(())
+    }
+
+    /// Queue a complete metric event to be sent on the next flush
+    pub async fn record(&self, event: MetricEvent) -> Result<(), SplunkError> {
+        event.validate()?;
+        self.state.lock().await.events.push(event);
+        Ok(())
+    }
+
+    /// How many metric events would be sent if we flushed now
+    pub async fn len(&self) -> usize {
+        let state = self.state.lock().await;
+        let mut dimensions: Vec<&Dimensions> = state.gauges.keys().collect();
+        dimensions.extend(state.counters.keys());
+        dimensions.sort();
+        dimensions.dedup();
+        dimensions.len() + state.events.len()
+    }
+
+    /// Is there anything waiting to be sent?
+    pub async fn is_empty(&self) -> bool {
+        self.len().await == 0
+    }
+
+    /// Send everything that's been recorded, returning the number of metric events sent.
+    ///
+    /// If a request fails, the metrics in it and anything after it are kept for the next flush. Gauges and counters go
+    /// back to being aggregated, so they're merged with anything recorded in the meantime. Requests which were accepted
+    /// before the failure aren't sent again.
+    pub async fn flush(&self) -> Result<usize, SplunkError> {
+        let RecorderState {
+            gauges,
+            mut counters,
+            events: recorded,
+        } = std::mem::take(&mut *self.state.lock().await);
+
+        let mut unsent: Vec<Unsent> = Vec::new();
+        for (dimensions, gauges) in gauges {
+            let counters = counters.remove(&dimensions).unwrap_or_default();
+            unsent.push(Unsent::Aggregated {
+                dimensions,
+                gauges,
+                counters,
+            });
+        }
+        for (dimensions, counters) in counters {
+            unsent.push(Unsent::Aggregated {
+                dimensions,
+                gauges: BTreeMap::new(),
+                counters,
+            });
+        }
+        unsent.extend(recorded.into_iter().map(Unsent::Recorded));
+
+        let now = SystemTime::now();
+        let mut encoded: Vec<(String, Unsent)> = Vec::with_capacity(unsent.len());
+        let mut unsent = unsent.into_iter();
+        while let Some(item) = unsent.next() {
+            let event = match &item {
+                Unsent::Aggregated {
+                    dimensions,
+                    gauges,
+                    counters,
+                } => {
+                    let mut measurements = gauges.clone();
+                    measurements
+                        .extend(counters.iter().map(|(name, value)| (name.clone(), *value)));
+                    MetricEvent {
+                        dimensions: dimensions.clone(),
+                        measurements,
+                        ..Default::default()
+                    }
+                    .with_time(now)
+                }
+                Unsent::Recorded(event) => event.clone(),
+            };
+            match event
+                .into_hec_event()
+                .and_then(|event| self.client.encode_event(&event))
+            {
+                Ok(json) => encoded.push((json, item)),
+                Err(err) => {
+                    let pending = encoded.into_iter().map(|(_, item)| item);
+                    self.state
+                        .lock()
+                        .await
+                        .restore(pending.chain([item]).chain(unsent));
+                    return Err(err);
+                }
+            }
+        }
+
+        let batch_size = self.batch_size.min(self.client.max_batch_events);
+        let mut batches = self
+            .client
+            .split_tagged_batches(encoded, batch_size)
+            .into_iter();
+        let mut sent: usize = 0;
+        while let Some((payload, items)) = batches.next() {
+            if let Err(err) = self.client.send_payload(payload).await {
+                // put the unsent metrics back so the next flush picks them up
+                let unsent = items
+                    .into_iter()
+                    .chain(batches.flat_map(|(_, items)| items));
+                self.state.lock().await.restore(unsent);
+                return Err(err);
+            }
+            sent += items.len();
+        }
+        Ok(sent)
+    }
+}
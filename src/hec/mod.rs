@@ -0,0 +1,364 @@
+//! HTTP Event Collector related functionality
+//!
+//! Based on <https://docs.splunk.com/Documentation/Splunk/9.0.4/Data/HECExamples>
+//!
+
+use std::collections::VecDeque;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use bytes::Bytes;
+use log::{debug, error};
+use reqwest::header::{HeaderMap, USER_AGENT};
+use reqwest::{Client, Url};
+use serde::de::DeserializeOwned;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::client::AuthenticationMethod;
+use crate::errors::SplunkError;
+use crate::http::Timeouts;
+use crate::retry::RetryPolicy;
+use crate::secret::Secret;
+use crate::ServerConfig;
+
+mod ack;
+mod balancer;
+mod batch;
+mod compression;
+mod event;
+mod flush;
+mod health;
+mod metrics;
+mod raw;
+mod response;
+mod sender;
+mod spool;
+
+pub use ack::{AckOptions, HEC_CHANNEL_HEADER};
+pub use balancer::{BalanceStrategy, BalancerConfig, HecBalancer, TargetStatus};
+pub use batch::{OversizedEventPolicy, DEFAULT_MAX_BATCH_EVENTS, DEFAULT_MAX_PAYLOAD_BYTES};
+pub use compression::GzipOptions;
+pub use event::{HecEvent, IntoHecEvent};
+#[cfg(test)]
+pub(crate) use flush::Disposition;
+pub use flush::{BatchError, FlushReport};
+pub use health::{HecHealth, HecHealthResult};
+pub use metrics::{MetricEvent, MetricsRecorder};
+pub use response::HecResponse;
+pub use sender::{HecSender, HecSenderConfig, HecSenderStats, OverflowPolicy};
+pub use spool::{HecSpool, SpoolConfig};
+
+const CONTENT_TYPE_JSON: &str = "application/json";
+
+/// How long a HEC request can take if the [ServerConfig] doesn't set a total timeout
+pub const DEFAULT_HEC_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// HEC Client
+#[derive(Debug)]
+pub struct HecClient {
+    /// See [ServerConfig]
+    pub serverconfig: ServerConfig,
+    /// The target index - if this is None then it'll just let the server decide
+    pub index: Option<String>,
+    /// The target sourcetype - if this is None then it'll just let the server decide
+    pub sourcetype: Option<String>,
+    /// The target source - if this is None then it'll just let the server decide
+    pub source: Option<String>,
+    /// The host field on events - if this is None then it'll just let the server decide
+    pub host: Option<String>,
+    queue: Arc<RwLock<VecDeque<HecEvent>>>,
+    /// The user-agent string to send, defaults to `splunk-rs <version>`
+    useragent: String,
+    /// How to retry failed sends, defaults to [RetryPolicy::none]
+    retry_policy: RetryPolicy,
+    /// The channel GUID sent in the `X-Splunk-Request-Channel` header
+    channel: Option<String>,
+    /// If set, wait for indexer acknowledgement when sending events
+    ack_options: Option<AckOptions>,
+    /// Maximum request body size in bytes, defaults to [DEFAULT_MAX_PAYLOAD_BYTES]
+    max_payload_bytes: usize,
+    /// Maximum events in a single request, defaults to [DEFAULT_MAX_BATCH_EVENTS]
+    max_batch_events: usize,
+    /// What to do with events too big to send
+    oversized_event_policy: OversizedEventPolicy,
+    /// If set, compress request bodies
+    gzip: Option<GzipOptions>,
+    /// If set, queued events are written to disk until they're sent
+    spool: Option<spool::AttachedSpool>,
+}
+
+impl Default for HecClient {
+    fn default() -> Self {
+        Self {
+            serverconfig: ServerConfig {
+                hostname: "localhost".to_string(),
+                port: 8088,
+                verify_tls: true,
+                auth_method: crate::client::AuthenticationMethod::Unknown,
+                ..Default::default()
+            },
+            index: None,
+            sourcetype: None,
+            source: None,
+            host: None,
+            queue: Arc::new(RwLock::new(VecDeque::new())),
+            useragent: crate::default_useragent(),
+            retry_policy: RetryPolicy::none(),
+            channel: None,
+            ack_options: None,
+            max_payload_bytes: DEFAULT_MAX_PAYLOAD_BYTES,
+            max_batch_events: DEFAULT_MAX_BATCH_EVENTS,
+            oversized_event_policy: OversizedEventPolicy::default(),
+            gzip: None,
+            spool: None,
+        }
+    }
+}
+
+/// The expected response from a health check
+#[deprecated(
+    note = "use HecHealthResult::health or HecClient::check_health, which understand every health state"
+)]
+pub static HEC_HEALTH_EXPECTED_RESPONSE: LazyLock<serde_json::Value> =
+    LazyLock::new(|| serde_json::json!({"text": "HEC is healthy", "code": 17}));
+
+impl HecClient {
+    /// Create a new HEC client, specifying the token and hostname. Defaults to port 8088
+    pub fn new(token: &str, hostname: &str) -> Self {
+        let serverconfig = ServerConfig::new(hostname.to_string())
+            .with_token(token.to_string())
+            .with_port(8088);
+        Self {
+            serverconfig,
+            ..Default::default()
+        }
+    }
+
+    /// Start the HEC Client with a given server config
+    pub fn with_serverconfig(serverconfig: ServerConfig) -> Self {
+        Self {
+            serverconfig,
+            ..Default::default()
+        }
+    }
+
+    /// Configure a custom user-agent string
+    pub fn useragent(&mut self, useragent: &str) {
+        self.useragent = useragent.to_string();
+    }
+
+    /// Set the index on the events you'll send
+    pub fn with_index(mut self, index: &str) -> Self {
+        self.index = Some(index.to_string());
+        self
+    }
+
+    /// Set the sourcetype on all events you send
+    pub fn with_sourcetype(mut self, sourcetype: &str) -> Self {
+        self.sourcetype = Some(sourcetype.to_string());
+        self
+    }
+
+    /// Set the source on all events you send
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = Some(source.to_string());
+        self
+    }
+
+    /// Set the host on all events you send
+    pub fn with_host(mut self, host: &str) -> Self {
+        self.host = Some(host.to_string());
+        self
+    }
+
+    /// Set the connect, read and total timeouts, see [ServerConfig::with_timeouts]
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.serverconfig = self.serverconfig.with_timeouts(timeouts);
+        self
+    }
+
+    /// Set the [RetryPolicy] used when sending events
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Send all events from this client on the given channel (a GUID) - this is required for indexer acknowledgement and the raw endpoint
+    pub fn with_channel(mut self, channel: &str) -> Self {
+        self.channel = Some(channel.to_string());
+        self
+    }
+
+    /// Send all events from this client on a new, randomly generated channel
+    pub fn with_random_channel(self) -> Self {
+        let channel = Uuid::new_v4().to_string();
+        self.with_channel(&channel)
+    }
+
+    /// The channel events are being sent on, if one's set
+    pub fn channel(&self) -> Option<&str> {
+        self.channel.as_deref()
+    }
+
+    /// Wait for indexer acknowledgement of every batch sent by [HecClient::send_events] (and so [HecClient::flush]).
+    ///
+    /// The token needs `useACK` enabled, and a random channel will be generated if you haven't set one.
+    pub fn with_indexer_ack(mut self, ack_options: AckOptions) -> Self {
+        if self.channel.is_none() {
+            self = self.with_random_channel();
+        }
+        self.ack_options = Some(ack_options);
+        self
+    }
+
+    /// Send a single event to the HEC endpoint, either something [serde::Serialize] or a [HecEvent]
+    pub async fn send_event(&self, event: impl IntoHecEvent) -> Result<HecResponse, SplunkError> {
+        let encoded = self.encode_event(&event.into_hec_event()?)?;
+        self.send_payload(encoded).await
+    }
+
+    /// The shared reqwest client, see [ServerConfig::with_http_config]
+    fn get_client(&self) -> Result<Client, SplunkError> {
+        self.serverconfig.http_client()
+    }
+
+    /// How long each request can take - the total from [ServerConfig::timeouts], or [DEFAULT_HEC_TIMEOUT]
+    fn request_timeout(&self) -> Duration {
+        self.serverconfig
+            .timeouts()
+            .total
+            .unwrap_or(DEFAULT_HEC_TIMEOUT)
+    }
+
+    /// The headers sent with every request to HEC - auth and the channel
+    fn get_headers(&self) -> Result<HeaderMap, SplunkError> {
+        let mut headers = HeaderMap::new();
+        let token = match &self.serverconfig.auth_method {
+            AuthenticationMethod::Token { token } => token.auth_header("Splunk")?,
+            AuthenticationMethod::Basic {
+                username: _,
+                password,
+            } => password.auth_header("Splunk")?,
+            AuthenticationMethod::Unknown => {
+                error!("Token is not set for HEC Event!");
+                Secret::default().auth_header("Splunk")?
+            }
+
+            // TODO: does HEC handle cookie auth? I don't think so?
+            AuthenticationMethod::Cookie { cookie: _ } => {
+                return Err(SplunkError::InvalidAuthmethod(
+                    "Cookie is not supported for HEC!",
+                ))
+            }
+        };
+        headers.insert("Authorization", token);
+        headers.insert(USER_AGENT, self.useragent.parse()?);
+        if let Some(channel) = &self.channel {
+            headers.insert(HEC_CHANNEL_HEADER, channel.parse()?);
+        }
+        Ok(headers)
+    }
+
+    /// Build the URL for a HEC endpoint, see [ServerConfig::get_url]
+    fn collector_url(&self, endpoint: &str) -> Result<Url, SplunkError> {
+        self.serverconfig
+            .get_url(endpoint)
+            .map_err(SplunkError::from)
+    }
+
+    /// POST a body to a HEC endpoint, retrying based on the [RetryPolicy] and parsing the response
+    async fn post_to_collector<T: DeserializeOwned + Default>(
+        &self,
+        endpoint: &str,
+        content_type: &str,
+        body: impl Into<Bytes>,
+    ) -> Result<T, SplunkError> {
+        let client = self.get_client()?;
+        let mut headers = self.get_headers()?;
+        headers.insert("Content-Type", content_type.parse()?);
+        let url = self.collector_url(endpoint)?;
+        let mut body: Bytes = body.into();
+        if let Some(gzip) = &self.gzip {
+            if let Some(compressed) = gzip.compress(&body)? {
+                debug!("Compressed {} bytes to {}", body.len(), compressed.len());
+                headers.insert("Content-Encoding", "gzip".parse()?);
+                body = compressed;
+            }
+        }
+
+        let result = self
+            .retry_policy
+            .execute(|| {
+                client
+                    .post(url.clone())
+                    .timeout(self.request_timeout())
+                    .headers(headers.clone())
+                    .body(body.clone())
+                    .send()
+            })
+            .await?;
+
+        let status = result.status();
+        // older servers send an empty body, so we don't insist on it parsing
+        let response = result.text().await?;
+        if !status.is_success() {
+            return Err(HecResponse::parse(&response).into_error(status.as_u16()));
+        }
+        Ok(serde_json::from_str(&response).unwrap_or_default())
+    }
+
+    /// Serialize the events, applying the [OversizedEventPolicy]
+    fn encode_events(&self, events: Vec<impl IntoHecEvent>) -> Result<Vec<String>, SplunkError> {
+        events
+            .into_iter()
+            // the event's own index, sourcetype, source and host win over the client's
+            .map(|event| self.encode_event(&event.into_hec_event()?))
+            .collect()
+    }
+
+    /// POST a newline-delimited payload to the event endpoint, waiting for acknowledgement if it's enabled
+    async fn send_payload(&self, payload: String) -> Result<HecResponse, SplunkError> {
+        let response: HecResponse = self
+            .post_to_collector("/services/collector", CONTENT_TYPE_JSON, payload)
+            .await?;
+        if let Some(ack_options) = &self.ack_options {
+            self.wait_for_ack(response.ack_id()?, ack_options).await?;
+        }
+        Ok(response)
+    }
+
+    /// send data to the HEC endpoint, split into as many requests as it takes to stay within the size limits
+    ///
+    /// If the client was configured [HecClient::with_indexer_ack] this waits until each batch has been acknowledged.
+    /// Returns the response to each request. If a request fails after others were accepted, the error is
+    /// [SplunkError::HecPartiallySent], so you can send just the rest again.
+    pub async fn send_events(
+        &self,
+        events: Vec<impl IntoHecEvent>,
+    ) -> Result<Vec<HecResponse>, SplunkError> {
+        let encoded = self.encode_events(events)?;
+        let mut responses = Vec::new();
+        let mut sent: usize = 0;
+        for (payload, events) in self.split_batches(encoded, self.max_batch_events) {
+            match self.send_payload(payload).await {
+                Ok(response) => responses.push(response),
+                Err(err) => return Err(SplunkError::partially_sent(responses, sent, err)),
+            }
+            sent += events;
+        }
+        Ok(responses)
+    }
+
+    /// add a new queue item, either something [serde::Serialize] or a [HecEvent]
+    pub async fn enqueue(&mut self, event: impl IntoHecEvent) -> Result<(), SplunkError> {
+        let event = event.into_hec_event()?;
+        self.enqueue_spooled(event).await
+    }
+
+    /// get the current queue size, including spooled events which haven't been read back from disk yet
+    pub async fn queue_size(&self) -> usize {
+        let backlog = self.spool.as_ref().map(|spool| spool.backlog).unwrap_or(0);
+        self.queue.read().await.len() + backlog
+    }
+}
@@ -0,0 +1,83 @@
+//! Sending to the raw endpoint, `/services/collector/raw`
+//!
+//! Raw data isn't wrapped in a JSON envelope, so it goes through the sourcetype's `props.conf` line
+//! breaking and timestamp extraction just like a file monitored by a forwarder. The index, source,
+//! sourcetype, host and channel are passed as query parameters.
+//!
+//! Based on <https://docs.splunk.com/Documentation/Splunk/latest/RESTREF/RESTinput#services.2Fcollector.2Fraw>
+
+use bytes::Bytes;
+
+use super::{HecClient, HecResponse};
+use crate::client::add_query_params_to_endpoint;
+use crate::errors::SplunkError;
+
+const CONTENT_TYPE_TEXT: &str = "text/plain";
+
+impl HecClient {
+    /// Build the raw endpoint URL, including the client's metadata as query parameters
+    pub(crate) fn raw_endpoint(&self) -> String {
+        let mut endpoint = "/services/collector/raw".to_string();
+        // in a fixed order, so the URL's the same every time
+        let params: Vec<(&str, &str)> = [
+            ("channel", &self.channel),
+            ("index", &self.index),
+            ("source", &self.source),
+            ("sourcetype", &self.sourcetype),
+            ("host", &self.host),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.as_deref().map(|value| (key, value)))
+        .collect();
+        add_query_params_to_endpoint(&mut endpoint, params);
+        endpoint
+    }
+
+    /// Send unwrapped data to the raw endpoint, the server does line breaking based on the sourcetype.
+    ///
+    /// Most servers require a channel for the raw endpoint, see [HecClient::with_channel].
+    /// If the client was configured [HecClient::with_indexer_ack] this waits until the data has been acknowledged.
+    pub async fn send_raw(&self, data: impl Into<Bytes>) -> Result<HecResponse, SplunkError> {
+        let data: Bytes = data.into();
+        if data.len() > self.max_payload_bytes {
+            return Err(SplunkError::PayloadTooLarge {
+                size: data.len(),
+                limit: self.max_payload_bytes,
+            });
+        }
+        let endpoint = self.raw_endpoint();
+        let response: HecResponse = self
+            .post_to_collector(&endpoint, CONTENT_TYPE_TEXT, data)
+            .await?;
+
+        if let Some(ack_options) = &self.ack_options {
+            self.wait_for_ack(response.ack_id()?, ack_options).await?;
+        }
+        Ok(response)
+    }
+
+    /// Send a set of lines to the raw endpoint separated by newlines, split into as many requests as it takes to stay
+    /// within [HecClient::with_max_payload_bytes]. Returns the response to each request.
+    ///
+    /// If a request fails after others were accepted, the error is [SplunkError::HecPartiallySent] with the number of
+    /// lines that were sent.
+    pub async fn send_raw_lines(
+        &self,
+        lines: &[impl AsRef<str>],
+    ) -> Result<Vec<HecResponse>, SplunkError> {
+        let lines = lines
+            .iter()
+            .map(|line| self.encode_raw_line(line.as_ref()))
+            .collect::<Result<Vec<&str>, SplunkError>>()?;
+        let mut responses = Vec::new();
+        let mut sent: usize = 0;
+        for (payload, lines) in self.split_batches(lines, usize::MAX) {
+            match self.send_raw(payload).await {
+                Ok(response) => responses.push(response),
+                Err(err) => return Err(SplunkError::partially_sent(responses, sent, err)),
+            }
+            sent += lines;
+        }
+        Ok(responses)
+    }
+}
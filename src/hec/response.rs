@@ -0,0 +1,76 @@
+//! What HEC sends back, and what its status codes mean
+//!
+//! Based on <https://docs.splunk.com/Documentation/Splunk/latest/Data/TroubleshootHTTPEventCollector#Possible_error_codes>
+
+use serde::Deserialize;
+
+use crate::errors::SplunkError;
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+/// The body HEC sends back after you send it something, eg `{"text":"Success","code":0,"ackId":4}`
+pub struct HecResponse {
+    /// The message from the server
+    #[serde(default)]
+    pub text: String,
+    /// The HEC status code, `0` for success
+    #[serde(default)]
+    pub code: Option<u32>,
+    /// The `ackId` for the request, if indexer acknowledgement is enabled
+    #[serde(default, rename = "ackId")]
+    pub ack_id: Option<u64>,
+    /// With code 6 (invalid data format), which event in the batch was invalid - counting from zero
+    #[serde(default, rename = "invalid-event-number")]
+    pub invalid_event_number: Option<usize>,
+}
+
+impl HecResponse {
+    /// Parse a response body, if it isn't JSON the whole body ends up in `text`
+    pub fn parse(body: &str) -> Self {
+        serde_json::from_str(body).unwrap_or_else(|_| Self {
+            text: body.to_string(),
+            ..Default::default()
+        })
+    }
+
+    /// Did the server accept the request?
+    pub fn is_success(&self) -> bool {
+        matches!(self.code, None | Some(0))
+    }
+
+    /// Get the `ackId`, or an error explaining why it's not there
+    pub(crate) fn ack_id(&self) -> Result<u64, SplunkError> {
+        self.ack_id.ok_or_else(|| {
+            SplunkError::HecAckUnavailable(format!(
+                "no ackId in the response, is useACK enabled on the token? Response: {}",
+                self.text
+            ))
+        })
+    }
+
+    /// Turn a failed response into the matching [SplunkError], based on the HEC status code
+    pub fn into_error(self, status: u16) -> SplunkError {
+        match self.code {
+            Some(1) => SplunkError::HecTokenDisabled(self),
+            Some(2) => SplunkError::HecTokenRequired(self),
+            Some(3) => SplunkError::HecInvalidAuthorization(self),
+            Some(4) => SplunkError::HecInvalidToken(self),
+            Some(5) => SplunkError::HecNoData(self),
+            Some(6) => SplunkError::HecInvalidDataFormat(self),
+            Some(7) => SplunkError::HecIncorrectIndex(self),
+            Some(8) => SplunkError::HecInternalError(self),
+            Some(9) => SplunkError::HecServerBusy(self),
+            Some(10) => SplunkError::HecDataChannelMissing(self),
+            Some(11) => SplunkError::HecInvalidDataChannel(self),
+            Some(12) => SplunkError::HecEventFieldRequired(self),
+            Some(13) => SplunkError::HecEventFieldBlank(self),
+            Some(14) => SplunkError::HecAckDisabled(self),
+            Some(15) => SplunkError::HecIndexedFieldsError(self),
+            Some(16) => SplunkError::HecQueryStringAuthDisabled(self),
+            Some(18..=20) => SplunkError::HecUnhealthy(self),
+            _ => SplunkError::HecRequestFailed {
+                status,
+                response: self,
+            },
+        }
+    }
+}
@@ -0,0 +1,340 @@
+//! A background task which owns the queue and flushes it for you
+//!
+//! ```no_run
+//! use splunk::hec::{HecClient, HecSender, HecSenderConfig};
+//! # async fn example() -> Result<(), splunk::errors::SplunkError> {
+//! let sender = HecSender::spawn(
+//!     HecClient::new("token", "localhost"),
+//!     HecSenderConfig::default(),
+//! );
+//! sender.send("hello world").await?;
+//! // waits until everything's been sent
+//! sender.shutdown().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, error, warn};
+use tokio::sync::{Mutex, Notify};
+use tokio::task::JoinHandle;
+
+use super::{HecClient, HecEvent, HecHealth, IntoHecEvent};
+use crate::errors::SplunkError;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// What [HecSender::send] does when the queue is full
+pub enum OverflowPolicy {
+    /// Wait until there's space in the queue
+    #[default]
+    Block,
+    /// Throw away the oldest queued event to make room. Events from failed batches are kept for retrying, so if
+    /// they're all that's queued the event being sent is thrown away instead.
+    DropOldest,
+    /// Throw away the event being sent
+    DropNewest,
+}
+
+#[derive(Clone, Debug)]
+/// Configuration for a [HecSender]
+pub struct HecSenderConfig {
+    /// Flush as soon as this many events are queued, defaults to 1000
+    pub batch_size: usize,
+    /// Flush at least this often while there's anything queued, defaults to 5 seconds
+    pub linger: Duration,
+    /// Maximum number of events waiting to be sent, defaults to 100,000. This includes events from failed batches which
+    /// are waiting to be retried.
+    pub capacity: usize,
+    /// What to do when the queue is full
+    pub overflow_policy: OverflowPolicy,
+    /// If set, check [HecClient::check_health] before the first flush and after any flush that fails, waiting this
+    /// long between checks until it's healthy. Defaults to off.
+    pub readiness_check: Option<Duration>,
+}
+
+impl Default for HecSenderConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 1000,
+            linger: Duration::from_secs(5),
+            capacity: 100_000,
+            overflow_policy: OverflowPolicy::default(),
+            readiness_check: None,
+        }
+    }
+}
+
+impl HecSenderConfig {
+    /// Set the number of queued events which triggers a flush
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Set the longest an event will wait before being flushed
+    pub fn with_linger(mut self, linger: Duration) -> Self {
+        self.linger = linger;
+        self
+    }
+
+    /// Set the maximum number of events waiting to be sent
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Set what happens when the queue is full
+    pub fn with_overflow_policy(mut self, overflow_policy: OverflowPolicy) -> Self {
+        self.overflow_policy = overflow_policy;
+        self
+    }
+
+    /// Wait for HEC to report it's healthy before flushing, checking every `interval`
+    pub fn with_readiness_check(mut self, interval: Duration) -> Self {
+        self.readiness_check = Some(interval);
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// Counters from a [HecSender]
+pub struct HecSenderStats {
+    /// Events successfully sent
+    pub sent: u64,
+    /// Events thrown away because the queue was full
+    pub dropped: u64,
+    /// Events which failed to send
+    pub failed: u64,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    queue: Mutex<VecDeque<HecEvent>>,
+    /// Events the background task has moved to the client's queue which haven't been sent yet
+    in_flight: AtomicUsize,
+    /// Wakes the background task
+    wake: Notify,
+    /// Wakes senders blocked on a full queue
+    space: Notify,
+    closed: AtomicBool,
+    sent: AtomicU64,
+    dropped: AtomicU64,
+    failed: AtomicU64,
+}
+
+/// Handle to a background task which batches events and sends them with a [HecClient].
+///
+/// The queue is flushed when it reaches [HecSenderConfig::batch_size] events, or every [HecSenderConfig::linger].
+/// Batches that fail for reasons worth retrying stay queued, and are retried after the linger before anything newer is
+/// sent. They count towards [HecSenderConfig::capacity], so a long outage fills the queue and the [OverflowPolicy]
+/// applies. Call [HecSender::shutdown] to send anything that's left and stop the task. Dropping the sender also tells
+/// the task to send what's left and stop, but doesn't wait for it.
+#[derive(Debug)]
+pub struct HecSender {
+    shared: Arc<Shared>,
+    config: HecSenderConfig,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl HecSender {
+    /// Start the background task on the current Tokio runtime
+    pub fn spawn(client: HecClient, config: HecSenderConfig) -> Self {
+        let shared = Arc::new(Shared::default());
+        let task = tokio::spawn(run(client, shared.clone(), config.clone()));
+        Self {
+            shared,
+            config,
+            task: Mutex::new(Some(task)),
+        }
+    }
+
+    /// Queue an event to be sent, either something [serde::Serialize] or a [HecEvent]
+    pub async fn send(&self, event: impl IntoHecEvent) -> Result<(), SplunkError> {
+        let event = event.into_hec_event()?;
+        loop {
+            // created before we check, so we can't miss a notification
+            let space = self.shared.space.notified();
+            let mut queue = self.shared.queue.lock().await;
+            // checked while holding the lock, so nothing's pushed after the final drain
+            if self.shared.closed.load(Ordering::SeqCst) {
+                return Err(SplunkError::HecSenderClosed);
+            }
+            let queued = queue.len() + self.shared.in_flight.load(Ordering::SeqCst);
+            if queued >= self.config.capacity {
+                match self.config.overflow_policy {
+                    OverflowPolicy::Block => {
+                        drop(queue);
+                        space.await;
+                        continue;
+                    }
+                    OverflowPolicy::DropOldest => {
+                        self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+                        if queue.pop_front().is_none() {
+                            return Ok(());
+                        }
+                    }
+                    OverflowPolicy::DropNewest => {
+                        self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+                        return Ok(());
+                    }
+                }
+            }
+            queue.push_back(event);
+            if queue.len() >= self.config.batch_size {
+                self.shared.wake.notify_one();
+            }
+            return Ok(());
+        }
+    }
+
+    /// The number of events waiting to be picked up by the background task
+    pub async fn queue_size(&self) -> usize {
+        self.shared.queue.lock().await.len()
+    }
+
+    /// Counts of what's happened so far
+    pub fn stats(&self) -> HecSenderStats {
+        HecSenderStats {
+            sent: self.shared.sent.load(Ordering::Relaxed),
+            dropped: self.shared.dropped.load(Ordering::Relaxed),
+            failed: self.shared.failed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Stop accepting events, send everything that's queued and wait for the background task to finish
+    pub async fn shutdown(&self) -> Result<HecSenderStats, SplunkError> {
+        {
+            let _queue = self.shared.queue.lock().await;
+            self.shared.closed.store(true, Ordering::SeqCst);
+        }
+        self.shared.wake.notify_one();
+        // anyone blocked on a full queue gets told we're closed
+        self.shared.space.notify_waiters();
+        if let Some(task) = self.task.lock().await.take() {
+            task.await
+                .map_err(|err| SplunkError::Generic(format!("HEC sender task failed: {err}")))?;
+        }
+        Ok(self.stats())
+    }
+}
+
+impl Drop for HecSender {
+    fn drop(&mut self) {
+        // nothing else can be sending while we're being dropped, so there's no need for the queue lock
+        self.shared.closed.store(true, Ordering::SeqCst);
+        self.shared.wake.notify_one();
+    }
+}
+
+/// The background task, moves events from the shared queue to the client and flushes them
+async fn run(mut client: HecClient, shared: Arc<Shared>, config: HecSenderConfig) {
+    let mut ready = config.readiness_check.is_none();
+    loop {
+        let closed = shared.closed.load(Ordering::SeqCst);
+        // events from a failed flush are still with the client
+        let retrying = client.queue_size().await > 0;
+        if !closed {
+            if retrying {
+                wait_to_retry(&shared, config.linger).await;
+            } else if shared.queue.lock().await.len() < config.batch_size {
+                tokio::select! {
+                    _ = shared.wake.notified() => {},
+                    _ = tokio::time::sleep(config.linger) => {},
+                }
+            }
+        }
+        // check again, so a shutdown while we were waiting still drains the queue
+        let closed = shared.closed.load(Ordering::SeqCst);
+
+        // newer events wait until the retries have gone, so they're bounded by the capacity
+        if closed || !retrying {
+            let events: Vec<HecEvent> = {
+                let mut queue = shared.queue.lock().await;
+                // counted while we hold the lock, so they're never missing from both
+                shared.in_flight.fetch_add(queue.len(), Ordering::SeqCst);
+                queue.drain(..).collect()
+            };
+            for event in events {
+                if let Err(err) = client.enqueue(event).await {
+                    error!("Failed to queue event: {err:?}");
+                    shared.failed.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+        if !ready && client.queue_size().await > 0 {
+            ready = wait_until_ready(&client, &shared, &config).await;
+        }
+        if client.queue_size().await > 0 {
+            let report = match client.flush(Some(config.batch_size as u32)).await {
+                Ok(report) => Some(report),
+                Err(SplunkError::FlushFailed(report)) => {
+                    error!("HEC sender failed to flush: {:?}", report.errors);
+                    ready = config.readiness_check.is_none();
+                    Some(*report)
+                }
+                Err(err) => {
+                    error!("HEC sender failed to flush: {err:?}");
+                    None
+                }
+            };
+            if let Some(report) = report {
+                debug!(
+                    "HEC sender flushed {} events, {} requeued",
+                    report.sent, report.requeued
+                );
+                shared.sent.fetch_add(report.sent as u64, Ordering::Relaxed);
+                shared
+                    .failed
+                    .fetch_add(report.dead_lettered.len() as u64, Ordering::Relaxed);
+            }
+        }
+        shared
+            .in_flight
+            .store(client.queue_size().await, Ordering::SeqCst);
+        shared.space.notify_waiters();
+
+        if closed {
+            // anything still queued after the last flush isn't going anywhere
+            let unsent = client.queue_size().await;
+            shared.failed.fetch_add(unsent as u64, Ordering::Relaxed);
+            break;
+        }
+    }
+}
+
+/// Wait for the linger before retrying a failed flush, only waking early to shut down
+async fn wait_to_retry(shared: &Shared, linger: Duration) {
+    let retry_at = tokio::time::Instant::now() + linger;
+    while !shared.closed.load(Ordering::SeqCst) {
+        tokio::select! {
+            _ = shared.wake.notified() => {},
+            _ = tokio::time::sleep_until(retry_at) => return,
+        }
+    }
+}
+
+/// Poll the health endpoint until HEC's ready, returning false if we gave up because we're shutting down
+async fn wait_until_ready(client: &HecClient, shared: &Shared, config: &HecSenderConfig) -> bool {
+    let Some(interval) = config.readiness_check else {
+        return true;
+    };
+    loop {
+        match client.check_health().await {
+            Ok(HecHealth::Healthy) => return true,
+            Ok(health) => warn!("HEC isn't ready: {health:?}"),
+            Err(err) => warn!("HEC health check failed: {err:?}"),
+        }
+        if shared.closed.load(Ordering::SeqCst) {
+            // give the final flush a go anyway
+            return false;
+        }
+        tokio::select! {
+            _ = shared.wake.notified() => {},
+            _ = tokio::time::sleep(interval) => {},
+        }
+    }
+}
@@ -0,0 +1,634 @@
+//! A disk-backed write-ahead spool, so queued events survive outages and restarts
+
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tokio::sync::{Mutex, MutexGuard};
+
+use super::{HecClient, HecEvent};
+use crate::errors::SplunkError;
+
+const SEGMENT_EXTENSION: &str = "spool";
+const CURSOR_FILENAME: &str = "cursor";
+/// Length and checksum
+const RECORD_HEADER_BYTES: usize = 8;
+
+#[derive(Clone, Debug)]
+/// Limits for a [HecSpool]
+pub struct SpoolConfig {
+    /// Start a new segment file once the current one is this big, defaults to 16MiB
+    pub max_segment_bytes: u64,
+    /// Maximum disk space used by the spool, the oldest segments are deleted to stay under it - defaults to 1GiB
+    pub max_total_bytes: u64,
+    /// Delete segments which haven't been written to for this long, defaults to never
+    pub max_age: Option<Duration>,
+    /// Call `fsync` after every write, defaults to true
+    pub sync_writes: bool,
+    /// Most spooled events to hold in the client's queue at once, the rest are read from disk as the queue's flushed.
+    /// Defaults to 100,000.
+    pub max_queued_events: usize,
+}
+
+impl Default for SpoolConfig {
+    fn default() -> Self {
+        Self {
+            max_segment_bytes: 16 * 1024 * 1024,
+            max_total_bytes: 1024 * 1024 * 1024,
+            max_age: None,
+            sync_writes: true,
+            max_queued_events: 100_000,
+        }
+    }
+}
+
+impl SpoolConfig {
+    /// Set the size at which a new segment file is started
+    pub fn with_max_segment_bytes(mut self, max_segment_bytes: u64) -> Self {
+        self.max_segment_bytes = max_segment_bytes.max(1);
+        self
+    }
+
+    /// Set the maximum disk space used by the spool
+    pub fn with_max_total_bytes(mut self, max_total_bytes: u64) -> Self {
+        self.max_total_bytes = max_total_bytes.max(1);
+        self
+    }
+
+    /// Delete segments which haven't been written to for this long
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Turn `fsync` after every write on or off
+    pub fn with_sync_writes(mut self, sync_writes: bool) -> Self {
+        self.sync_writes = sync_writes;
+        self
+    }
+
+    /// Set the most spooled events to hold in the client's queue at once
+    pub fn with_max_queued_events(mut self, max_queued_events: usize) -> Self {
+        self.max_queued_events = max_queued_events.max(1);
+        self
+    }
+}
+
+/// How a [HecEvent] is stored on disk
+#[derive(Serialize, Deserialize)]
+struct SpooledEvent {
+    event: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    time: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    index: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sourcetype: Option<String>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    fields: Map<String, Value>,
+}
+
+impl From<&HecEvent> for SpooledEvent {
+    fn from(value: &HecEvent) -> Self {
+        Self {
+            event: value.event.clone(),
+            time: value.time,
+            host: value.host.clone(),
+            index: value.index.clone(),
+            source: value.source.clone(),
+            sourcetype: value.sourcetype.clone(),
+            fields: value.fields.clone(),
+        }
+    }
+}
+
+impl From<SpooledEvent> for HecEvent {
+    fn from(value: SpooledEvent) -> Self {
+        Self {
+            event: value.event,
+            time: value.time,
+            host: value.host,
+            index: value.index,
+            source: value.source,
+            sourcetype: value.sourcetype,
+            fields: value.fields,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Segment {
+    id: u64,
+    path: PathBuf,
+    bytes: u64,
+    records: u64,
+}
+
+/// A disk-backed queue of events, so they survive outages and restarts
+///
+/// Events are appended to segment files as they're queued, and only removed once a flush has sent them (and they've
+/// been acknowledged, if [HecClient::with_indexer_ack] is on). When a client is attached to a spool, anything left
+/// over from last time is put back on the queue, up to [SpoolConfig::max_queued_events] at a time. The client does
+/// its spool IO on Tokio's blocking thread pool.
+/// Events waiting in a [super::HecSender]'s own queue aren't spooled until they reach the client, see
+/// [HecClient::attach_spool].
+///
+/// Each record in a segment is a little-endian `u32` length, a `u32` CRC32 of the data, then the event as JSON.
+/// A record that fails its checksum (say, from a crash part way through a write) ends that segment.
+///
+/// ```no_run
+/// use splunk::hec::{HecClient, HecSpool, SpoolConfig};
+/// # async fn example() -> Result<(), splunk::errors::SplunkError> {
+/// let spool = HecSpool::open("/var/spool/my-app", SpoolConfig::default())?;
+/// let mut client = HecClient::new("token", "localhost");
+/// let replayed = client.attach_spool(spool).await?;
+/// println!("{replayed} events left over from last time");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct HecSpool {
+    dir: PathBuf,
+    config: SpoolConfig,
+    segments: VecDeque<Segment>,
+    /// The segment being appended to - always the last one in `segments`
+    writer: Option<File>,
+    /// Records already sent from the first segment
+    committed: u64,
+}
+
+impl HecSpool {
+    /// Open (or create) a spool in the given directory
+    pub fn open(dir: impl AsRef<Path>, config: SpoolConfig) -> Result<Self, SplunkError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let mut ids: Vec<u64> = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(SEGMENT_EXTENSION) {
+                continue;
+            }
+            if let Some(id) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<u64>().ok())
+            {
+                ids.push(id);
+            }
+        }
+        ids.sort_unstable();
+
+        let (cursor_segment, mut committed) = read_cursor(&dir)?;
+
+        let mut segments: VecDeque<Segment> = VecDeque::new();
+        let last_id = ids.last().copied();
+        for id in ids {
+            let path = segment_path(&dir, id);
+            if id < cursor_segment {
+                debug!("Removing already-sent spool segment {}", path.display());
+                fs::remove_file(&path)?;
+                continue;
+            }
+            let data = fs::read(&path)?;
+            let (records, valid_bytes) = scan_records(&data);
+            if valid_bytes < data.len() {
+                warn!(
+                    "Spool segment {} is corrupt after {} records, ignoring the last {} bytes",
+                    path.display(),
+                    records.len(),
+                    data.len() - valid_bytes
+                );
+                if Some(id) == last_id {
+                    // we'll be appending to this one, so cut off the damaged tail
+                    OpenOptions::new()
+                        .write(true)
+                        .open(&path)?
+                        .set_len(valid_bytes as u64)?;
+                }
+            }
+            segments.push_back(Segment {
+                id,
+                path,
+                bytes: valid_bytes as u64,
+                records: records.len() as u64,
+            });
+        }
+        if segments.front().map(|segment| segment.id) != Some(cursor_segment) {
+            // the segment the cursor pointed at is gone, so nothing in the first one has been sent
+            committed = 0;
+        }
+
+        let mut spool = Self {
+            dir,
+            config,
+            segments,
+            writer: None,
+            committed,
+        };
+        spool.apply_retention()?;
+        spool.write_cursor()?;
+        Ok(spool)
+    }
+
+    /// The directory the spool lives in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The number of events waiting to be sent
+    pub fn len(&self) -> u64 {
+        let total: u64 = self.segments.iter().map(|segment| segment.records).sum();
+        total.saturating_sub(self.committed)
+    }
+
+    /// Is there anything waiting to be sent?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Disk space used by the segment files
+    pub fn disk_usage(&self) -> u64 {
+        self.segments.iter().map(|segment| segment.bytes).sum()
+    }
+
+    /// Read the events waiting to be sent, oldest first.
+    ///
+    /// Segments are read as the iterator gets to them, so only one is in memory at a time.
+    pub fn pending(&self) -> impl Iterator<Item = Result<HecEvent, SplunkError>> + '_ {
+        self.segments
+            .iter()
+            .enumerate()
+            .flat_map(move |(position, segment)| {
+                let skip = match position {
+                    0 => self.committed as usize,
+                    _ => 0,
+                };
+                match read_segment(&segment.path, skip) {
+                    Ok(events) => events.into_iter().map(Ok).collect(),
+                    Err(err) => vec![Err(err)],
+                }
+            })
+    }
+
+    /// Write events to the end of the spool.
+    ///
+    /// Returns the number of unsent events which were deleted to stay within the [SpoolConfig] limits.
+    pub fn append(&mut self, events: &[HecEvent]) -> Result<u64, SplunkError> {
+        if events.is_empty() {
+            return Ok(0);
+        }
+        let mut buffer: Vec<u8> = Vec::new();
+        for event in events {
+            let data = serde_json::to_vec(&SpooledEvent::from(event))?;
+            let length = u32::try_from(data.len()).map_err(|_| SplunkError::PayloadTooLarge {
+                size: data.len(),
+                limit: u32::MAX as usize,
+            })?;
+            buffer.extend_from_slice(&length.to_le_bytes());
+            buffer.extend_from_slice(&crc32fast::hash(&data).to_le_bytes());
+            buffer.extend_from_slice(&data);
+        }
+
+        let needs_new_segment = match self.segments.back() {
+            Some(segment) => segment.bytes >= self.config.max_segment_bytes,
+            None => true,
+        };
+        if needs_new_segment {
+            let id = self
+                .segments
+                .back()
+                .map(|segment| segment.id + 1)
+                .unwrap_or(0);
+            let path = segment_path(&self.dir, id);
+            debug!("Starting spool segment {}", path.display());
+            self.writer = None;
+            self.segments.push_back(Segment {
+                id,
+                path,
+                bytes: 0,
+                records: 0,
+            });
+        }
+
+        let Some(segment) = self.segments.back_mut() else {
+            return Err(SplunkError::Generic(
+                "Spool has no segment to write to".to_string(),
+            ));
+        };
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => self.writer.insert(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&segment.path)?,
+            ),
+        };
+        writer.write_all(&buffer)?;
+        if self.config.sync_writes {
+            writer.sync_data()?;
+        }
+        segment.bytes += buffer.len() as u64;
+        segment.records += events.len() as u64;
+
+        self.apply_retention()
+    }
+
+    /// Mark the oldest `count` events as sent, deleting segment files once everything in them has been sent
+    pub fn commit(&mut self, count: u64) -> Result<(), SplunkError> {
+        let mut count = count;
+        while count > 0 {
+            let Some(segment) = self.segments.front() else {
+                break;
+            };
+            let remaining = segment.records.saturating_sub(self.committed);
+            if count < remaining {
+                self.committed += count;
+                break;
+            }
+            count -= remaining;
+            self.remove_first_segment()?;
+        }
+        self.write_cursor()
+    }
+
+    /// Drop the first segment, and the writer if it's the one being written to
+    fn remove_first_segment(&mut self) -> Result<(), SplunkError> {
+        if let Some(segment) = self.segments.pop_front() {
+            if self.segments.is_empty() {
+                self.writer = None;
+            }
+            debug!("Removing spool segment {}", segment.path.display());
+            fs::remove_file(&segment.path)?;
+        }
+        self.committed = 0;
+        Ok(())
+    }
+
+    /// Delete the oldest segments until we're within the limits, returning how many unsent events were lost
+    fn apply_retention(&mut self) -> Result<u64, SplunkError> {
+        let mut dropped: u64 = 0;
+        while let Some(segment) = self.segments.front() {
+            let too_big =
+                self.disk_usage() > self.config.max_total_bytes && self.segments.len() > 1;
+            let too_old = match self.config.max_age {
+                Some(max_age) => fs::metadata(&segment.path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+                    .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                    .map(|age| age > max_age)
+                    .unwrap_or(false),
+                None => false,
+            };
+            if !too_big && !too_old {
+                break;
+            }
+            let lost = segment.records.saturating_sub(self.committed);
+            warn!(
+                "Spool retention limit reached, deleting {} with {} unsent events",
+                segment.path.display(),
+                lost
+            );
+            dropped += lost;
+            self.remove_first_segment()?;
+        }
+        if dropped > 0 {
+            self.write_cursor()?;
+        }
+        Ok(dropped)
+    }
+
+    /// Save our position, writing to a temporary file first so a crash can't leave it half-written
+    fn write_cursor(&self) -> Result<(), SplunkError> {
+        let segment = self.segments.front().map(|segment| segment.id).unwrap_or(0);
+        let temp_path = self.dir.join(format!("{CURSOR_FILENAME}.tmp"));
+        fs::write(&temp_path, format!("{} {}", segment, self.committed))?;
+        fs::rename(&temp_path, self.dir.join(CURSOR_FILENAME))?;
+        Ok(())
+    }
+}
+
+fn segment_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{id:020}.{SEGMENT_EXTENSION}"))
+}
+
+/// Read the segment ID and number of committed records, defaults to the start
+fn read_cursor(dir: &Path) -> Result<(u64, u64), SplunkError> {
+    let path = dir.join(CURSOR_FILENAME);
+    if !path.exists() {
+        return Ok((0, 0));
+    }
+    let contents = fs::read_to_string(&path)?;
+    let mut parts = contents.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some(segment), Some(committed)) => Ok((segment.parse()?, committed.parse()?)),
+        _ => {
+            warn!("Ignoring invalid spool cursor file {}", path.display());
+            Ok((0, 0))
+        }
+    }
+}
+
+/// Read the events in a segment file, skipping the first `skip`
+fn read_segment(path: &Path, skip: usize) -> Result<Vec<HecEvent>, SplunkError> {
+    let data = fs::read(path)?;
+    let (records, _) = scan_records(&data);
+    records
+        .into_iter()
+        .skip(skip)
+        .map(|record| Ok(serde_json::from_slice::<SpooledEvent>(record)?.into()))
+        .collect()
+}
+
+/// Split a segment into records, stopping at the first one that's truncated or fails its checksum.
+///
+/// Returns the records and the number of bytes that were valid.
+fn scan_records(data: &[u8]) -> (Vec<&[u8]>, usize) {
+    let mut records = Vec::new();
+    let mut offset: usize = 0;
+    while let Some(header) = data.get(offset..offset + RECORD_HEADER_BYTES) {
+        let (length, checksum) = header.split_at(4);
+        let (Ok(length), Ok(checksum)) = (length.try_into(), checksum.try_into()) else {
+            break;
+        };
+        let length = u32::from_le_bytes(length) as usize;
+        let checksum = u32::from_le_bytes(checksum);
+        let start = offset + RECORD_HEADER_BYTES;
+        let Some(record) = data.get(start..start + length) else {
+            break;
+        };
+        if crc32fast::hash(record) != checksum {
+            break;
+        }
+        records.push(record);
+        offset = start + length;
+    }
+    (records, offset)
+}
+
+/// A spool attached to a client with [HecClient::attach_spool].
+///
+/// The spool's unsent events are the client's queue followed by `backlog` events which are only on disk.
+#[derive(Debug)]
+pub(crate) struct AttachedSpool {
+    spool: Arc<Mutex<HecSpool>>,
+    /// Most events to keep in the client's queue, see [SpoolConfig::max_queued_events]
+    max_queued: usize,
+    /// Events after the ones in the queue which haven't been read back from disk yet
+    pub(crate) backlog: usize,
+}
+
+/// Run spool IO on the blocking thread pool, so reads, writes and fsyncs don't hold up the runtime
+async fn spool_io<T: Send + 'static>(
+    spool: &Arc<Mutex<HecSpool>>,
+    io: impl FnOnce(&mut HecSpool) -> Result<T, SplunkError> + Send + 'static,
+) -> Result<T, SplunkError> {
+    let spool = spool.clone();
+    tokio::task::spawn_blocking(move || io(&mut spool.blocking_lock()))
+        .await
+        .map_err(|err| SplunkError::Generic(format!("Spool IO failed: {err}")))?
+}
+
+impl HecClient {
+    /// Write queued events to a spool, so they survive the process restarting.
+    ///
+    /// Anything left in the spool goes ahead of anything already queued, which is added to the spool. Only the first
+    /// [SpoolConfig::max_queued_events] are read into the queue, the rest are read as the queue's flushed. Returns the
+    /// number of events that were left in the spool.
+    ///
+    /// Only events in this client's queue are spooled. If it's being driven by a [super::HecSender], events still in
+    /// the sender's own queue are handed to the client (and spooled) by [super::HecSender::shutdown], but they're lost
+    /// if the process dies before then. Use a short [super::HecSenderConfig::linger], or call [HecClient::enqueue]
+    /// yourself, if every event has to survive a crash.
+    pub async fn attach_spool(&mut self, spool: HecSpool) -> Result<usize, SplunkError> {
+        let max_queued = spool.config.max_queued_events;
+        let spool = Arc::new(Mutex::new(spool));
+        let queued: Vec<HecEvent> = self.queue.read().await.iter().cloned().collect();
+        let queued_count = queued.len();
+
+        let (replayed, pending, dropped) = spool_io(&spool, move |spool| {
+            let pending = spool.len() as usize;
+            let replayed = spool
+                .pending()
+                .take(max_queued)
+                .collect::<Result<Vec<HecEvent>, SplunkError>>()?;
+            let dropped = spool.append(&queued)?;
+            Ok((replayed, pending, dropped as usize))
+        })
+        .await?;
+
+        let queue = self.queue.clone();
+        let mut queue = queue.write().await;
+        let backlog = match replayed.len() < pending {
+            // the queued events are on disk now, behind the ones we didn't have room for
+            true => {
+                queue.clear();
+                pending - replayed.len() + queued_count
+            }
+            false => 0,
+        };
+        for event in replayed.into_iter().rev() {
+            queue.push_front(event);
+        }
+        self.spool = Some(AttachedSpool {
+            spool,
+            max_queued,
+            backlog,
+        });
+        self.forget_spooled(&mut queue, dropped);
+        Ok(pending)
+    }
+
+    /// The spool attached with [HecClient::attach_spool], if there is one. It's locked until the guard's dropped.
+    pub async fn spool(&self) -> Option<MutexGuard<'_, HecSpool>> {
+        match &self.spool {
+            Some(attached) => Some(attached.spool.lock().await),
+            None => None,
+        }
+    }
+
+    /// Add an event to the spool, and the queue if it's not got a backlog on disk
+    pub(crate) async fn enqueue_spooled(&mut self, event: HecEvent) -> Result<(), SplunkError> {
+        let Some(attached) = self.spool.as_ref() else {
+            self.queue.write().await.push_back(event);
+            return Ok(());
+        };
+        let (event, dropped) = spool_io(&attached.spool, move |spool| {
+            let dropped = spool.append(std::slice::from_ref(&event))?;
+            Ok((event, dropped as usize))
+        })
+        .await?;
+
+        let queue = self.queue.clone();
+        let mut queue = queue.write().await;
+        if let Some(attached) = self.spool.as_mut() {
+            match attached.backlog == 0 && queue.len() < attached.max_queued {
+                true => queue.push_back(event),
+                // it's safe on disk, and it'll be read back after the ones before it
+                false => attached.backlog += 1,
+            }
+        }
+        self.forget_spooled(&mut queue, dropped);
+        Ok(())
+    }
+
+    /// Retention threw away the oldest spooled events, keep the queue and backlog in step
+    fn forget_spooled(&mut self, queue: &mut VecDeque<HecEvent>, dropped: usize) {
+        let from_queue = dropped.min(queue.len());
+        queue.drain(..from_queue);
+        if let Some(attached) = self.spool.as_mut() {
+            attached.backlog = attached.backlog.saturating_sub(dropped - from_queue);
+        }
+    }
+
+    /// Mark events as done in the spool, if there is one
+    pub(crate) async fn commit_spool(&self, count: usize) -> Result<(), SplunkError> {
+        match &self.spool {
+            Some(attached) => {
+                spool_io(&attached.spool, move |spool| spool.commit(count as u64)).await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Read more of the spool's backlog into the queue, if it's running low
+    pub(crate) async fn refill_from_spool(&mut self, batch_size: usize) -> Result<(), SplunkError> {
+        let Some(attached) = self.spool.as_ref() else {
+            return Ok(());
+        };
+        // the queue's the start of what's pending, so carry on from the end of it
+        let skip = self.queue.read().await.len();
+        if attached.backlog == 0 || skip >= batch_size {
+            return Ok(());
+        }
+        let take = attached.max_queued.max(batch_size) - skip;
+        let loaded = spool_io(&attached.spool, move |spool| {
+            spool
+                .pending()
+                .skip(skip)
+                .take(take)
+                .collect::<Result<Vec<HecEvent>, SplunkError>>()
+        })
+        .await?;
+        if let Some(attached) = self.spool.as_mut() {
+            attached.backlog = match loaded.is_empty() {
+                // the spool's lost them somehow, don't keep looking
+                true => 0,
+                false => attached.backlog.saturating_sub(loaded.len()),
+            };
+        }
+        self.queue.write().await.extend(loaded);
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+//! Proxy, connection pool and timeout settings for the HTTP client
+//!
+//! Each [ServerConfig] builds its HTTP client once, the first time it's needed, and shares it with its clones - so a
+//! [crate::hec::HecClient] and a [crate::client::SplunkClient] made from the same config reuse the same connections.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use splunk::http::HttpConfig;
+//! use splunk::ServerConfig;
+//!
+//! let http = HttpConfig::default()
+//!     .with_proxy("socks5h://proxy.example.com:1080")
+//!     .with_no_proxy("localhost,.internal.example.com")
+//!     .with_pool_max_idle_per_host(16)
+//!     .with_tcp_keepalive(Duration::from_secs(30));
+//! let serverconfig = ServerConfig::new("splunk.example.com".to_string()).with_http_config(http);
+//! ```
+//!
+//! [Timeouts] come in three kinds: connecting, waiting between reads, and the whole request. The first two are set on
+//! the shared client, the total is applied to each request and can be overridden per call - see
+//! [crate::client::SplunkClient::do_get_with_timeout].
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use splunk::http::Timeouts;
+//! use splunk::ServerConfig;
+//!
+//! let timeouts = Timeouts::default()
+//!     .with_connect(Duration::from_secs(5))
+//!     .with_read(Duration::from_secs(30))
+//!     .with_total(Duration::from_secs(60));
+//! let serverconfig = ServerConfig::new("splunk.example.com".to_string()).with_timeouts(timeouts);
+//! ```
+
+use std::time::Duration;
+
+use reqwest::{ClientBuilder, NoProxy, Proxy, RequestBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::SplunkError;
+#[cfg(doc)]
+use crate::ServerConfig;
+
+#[derive(Clone, Debug, Default)]
+/// How the HTTP client connects, see the [module documentation](self)
+pub struct HttpConfig {
+    /// Send everything through this proxy - `http://`, `https://`, `socks5://` or `socks5h://`, with credentials in
+    /// the URL if it needs them. If it's not set, the `HTTPS_PROXY`/`HTTP_PROXY` environment variables are used.
+    pub proxy: Option<String>,
+    /// Comma-separated hosts, domains and networks which skip the proxy, like the `NO_PROXY` environment variable
+    pub no_proxy: Option<String>,
+    /// Maximum idle connections kept open to each host, defaults to no limit
+    pub pool_max_idle_per_host: Option<usize>,
+    /// Close idle connections after this long, defaults to 90 seconds
+    pub pool_idle_timeout: Option<Duration>,
+    /// Send TCP keepalives this often, defaults to off
+    pub tcp_keepalive: Option<Duration>,
+    /// Talk HTTP/2 straight away rather than negotiating it, defaults to false
+    pub http2_prior_knowledge: bool,
+}
+
+impl HttpConfig {
+    /// Send everything through a proxy
+    pub fn with_proxy(mut self, proxy: &str) -> Self {
+        self.proxy = Some(proxy.to_string());
+        self
+    }
+
+    /// Set the hosts which skip the proxy
+    pub fn with_no_proxy(mut self, no_proxy: &str) -> Self {
+        self.no_proxy = Some(no_proxy.to_string());
+        self
+    }
+
+    /// Set the maximum idle connections kept open to each host
+    pub fn with_pool_max_idle_per_host(mut self, max_idle: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max_idle);
+        self
+    }
+
+    /// Close idle connections after this long
+    pub fn with_pool_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Send TCP keepalives this often
+    pub fn with_tcp_keepalive(mut self, keepalive: Duration) -> Self {
+        self.tcp_keepalive = Some(keepalive);
+        self
+    }
+
+    /// Talk HTTP/2 straight away rather than negotiating it
+    pub fn with_http2_prior_knowledge(mut self, http2_prior_knowledge: bool) -> Self {
+        self.http2_prior_knowledge = http2_prior_knowledge;
+        self
+    }
+
+    /// Apply the settings to a client builder
+    pub(crate) fn apply(&self, mut builder: ClientBuilder) -> Result<ClientBuilder, SplunkError> {
+        if let Some(proxy) = &self.proxy {
+            let proxy = Proxy::all(proxy)?;
+            let proxy = match &self.no_proxy {
+                Some(no_proxy) => proxy.no_proxy(NoProxy::from_string(no_proxy)),
+                None => proxy,
+            };
+            builder = builder.proxy(proxy);
+        }
+        if let Some(max_idle) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max_idle);
+        }
+        if let Some(idle_timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(idle_timeout);
+        }
+        if let Some(keepalive) = self.tcp_keepalive {
+            builder = builder.tcp_keepalive(keepalive);
+        }
+        if self.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+        Ok(builder)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// How long to wait for the server, see the [module documentation](self).
+///
+/// When one runs out, the request fails with [SplunkError::Timeout].
+pub struct Timeouts {
+    /// How long to wait for the connection (including the TLS handshake), defaults to 30 seconds
+    pub connect: Option<Duration>,
+    /// How long to wait for each read from the server, defaults to no limit
+    pub read: Option<Duration>,
+    /// How long the whole request can take, including reading the body, defaults to no limit
+    pub total: Option<Duration>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: Some(Duration::from_secs(30)),
+            read: None,
+            total: None,
+        }
+    }
+}
+
+impl Timeouts {
+    /// Set how long to wait for the connection
+    pub fn with_connect(mut self, connect: Duration) -> Self {
+        self.connect = Some(connect);
+        self
+    }
+
+    /// Set how long to wait for each read from the server
+    pub fn with_read(mut self, read: Duration) -> Self {
+        self.read = Some(read);
+        self
+    }
+
+    /// Set how long the whole request can take
+    pub fn with_total(mut self, total: Duration) -> Self {
+        self.total = Some(total);
+        self
+    }
+
+    /// Apply the connect and read timeouts to a client builder
+    pub(crate) fn apply(&self, mut builder: ClientBuilder) -> ClientBuilder {
+        if let Some(connect) = self.connect {
+            builder = builder.connect_timeout(connect);
+        }
+        if let Some(read) = self.read {
+            builder = builder.read_timeout(read);
+        }
+        builder
+    }
+}
+
+/// Set the total timeout on a request, if there is one
+pub(crate) fn with_total_timeout(
+    request: RequestBuilder,
+    total: Option<Duration>,
+) -> RequestBuilder {
+    match total {
+        Some(total) => request.timeout(total),
+        None => request,
+    }
+}
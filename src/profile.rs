@@ -0,0 +1,351 @@
+//! Named connection profiles from a `.splunkrc` TOML file
+//!
+//! The file is found with the `SPLUNKRC` environment variable, falling back to `~/.splunkrc`. Each profile sets where
+//! to connect and how to authenticate, and the usual `SPLUNK_HEC_*`/`SPLUNK_API_*` environment variables override it.
+//!
+//! ```toml
+//! default_profile = "dev"
+//!
+//! [profiles.dev]
+//! hostname = "localhost"
+//! verify_tls = false
+//! username = "admin"
+//! password_file = "~/.splunk/dev.password"
+//! hec_token_file = "~/.splunk/dev.hec"
+//!
+//! [profiles.prod]
+//! hostname = "splunk.example.com"
+//! port = 443
+//! base_path = "/splunk"
+//! app = "search"
+//! owner = "nobody"
+//! token_command = ["pass", "show", "splunk/prod-token"]
+//! ca_file = "/etc/pki/splunk-ca.pem"
+//! ```
+//!
+//! ```no_run
+//! use splunk::{ServerConfig, ServerConfigType};
+//! # fn example() -> Result<(), splunk::errors::SplunkError> {
+//! let serverconfig = ServerConfig::from_splunkrc(None, Some("prod"), ServerConfigType::Api)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::errors::SplunkError;
+use crate::secret::Secret;
+use crate::tls::TlsConfig;
+use crate::{ServerConfig, ServerConfigType};
+
+/// The environment variable pointing at the config file
+pub const SPLUNKRC_ENV: &str = "SPLUNKRC";
+
+/// The environment variable naming the profile to use, if one isn't asked for
+pub const PROFILE_ENV: &str = "SPLUNK_PROFILE";
+
+/// The profile used when nothing else picks one
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+/// The contents of a `.splunkrc` file, see the [module documentation](self)
+pub struct SplunkRc {
+    /// The profile to use if one isn't asked for and `SPLUNK_PROFILE` isn't set
+    pub default_profile: Option<String>,
+    /// The profiles, by name
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl FromStr for SplunkRc {
+    type Err = SplunkError;
+
+    fn from_str(contents: &str) -> Result<Self, Self::Err> {
+        toml::from_str(contents).map_err(SplunkError::from)
+    }
+}
+
+impl SplunkRc {
+    /// Where the config file should be - `$SPLUNKRC`, or `~/.splunkrc`
+    pub fn default_path() -> Option<PathBuf> {
+        match env::var_os(SPLUNKRC_ENV) {
+            Some(path) => Some(PathBuf::from(path)),
+            None => home_dir().map(|home| home.join(".splunkrc")),
+        }
+    }
+
+    /// Load the config file from [SplunkRc::default_path]
+    pub fn load() -> Result<Self, SplunkError> {
+        let path = Self::default_path().ok_or_else(|| {
+            SplunkError::Generic(format!(
+                "Couldn't find a config file, set {SPLUNKRC_ENV} or HOME"
+            ))
+        })?;
+        Self::from_file(path)
+    }
+
+    /// Load a config file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, SplunkError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|err| {
+            SplunkError::Generic(format!("Failed to read {}: {}", path.display(), err))
+        })?;
+        contents.parse().map_err(|err| match err {
+            SplunkError::Generic(message) => {
+                SplunkError::Generic(format!("{}: {}", path.display(), message))
+            }
+            err => err,
+        })
+    }
+
+    /// The name of the profile to use - `name` if it's given, then `$SPLUNK_PROFILE`, then `default_profile`, then
+    /// [DEFAULT_PROFILE]
+    pub fn profile_name(&self, name: Option<&str>) -> String {
+        name.map(str::to_string)
+            .or_else(|| env::var(PROFILE_ENV).ok())
+            .or_else(|| self.default_profile.clone())
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+    }
+
+    /// Get a profile, picked by [SplunkRc::profile_name]
+    pub fn profile(&self, name: Option<&str>) -> Result<&Profile, SplunkError> {
+        let name = self.profile_name(name);
+        self.profiles.get(&name).ok_or_else(|| {
+            SplunkError::Generic(format!(
+                "No profile called {name:?}, the config file has {:?}",
+                self.profiles.keys().collect::<Vec<_>>()
+            ))
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// How to talk to the server
+pub enum Scheme {
+    /// Plain HTTP
+    Http,
+    /// HTTP over TLS
+    #[default]
+    Https,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+/// One named environment in a [SplunkRc]. Paths can start with `~/`.
+///
+/// Each credential can be given inline, in a file or by a command - the first one set is used.
+pub struct Profile {
+    /// Server hostname
+    pub hostname: Option<String>,
+    /// REST API port, defaults to 8089
+    pub port: Option<u16>,
+    /// HEC port, defaults to 8088
+    pub hec_port: Option<u16>,
+    /// `https` (the default) or `http`
+    pub scheme: Option<Scheme>,
+    /// Check the server's certificate, defaults to true
+    pub verify_tls: Option<bool>,
+    /// Prefix for every endpoint, for servers behind a reverse proxy
+    pub base_path: Option<String>,
+    /// The app namespace for REST calls
+    pub app: Option<String>,
+    /// The owner (user) namespace for REST calls
+    pub owner: Option<String>,
+    /// REST API token
+    pub token: Option<Secret>,
+    /// File holding the REST API token
+    pub token_file: Option<PathBuf>,
+    /// Command which prints the REST API token, like `["pass", "show", "splunk/prod-token"]`
+    pub token_command: Option<Vec<String>>,
+    /// Username for REST API basic auth
+    pub username: Option<String>,
+    /// Password for REST API basic auth
+    pub password: Option<Secret>,
+    /// File holding the password for REST API basic auth
+    pub password_file: Option<PathBuf>,
+    /// Command which prints the password for REST API basic auth
+    pub password_command: Option<Vec<String>>,
+    /// HEC token
+    pub hec_token: Option<Secret>,
+    /// File holding the HEC token
+    pub hec_token_file: Option<PathBuf>,
+    /// Command which prints the HEC token
+    pub hec_token_command: Option<Vec<String>>,
+    /// PEM file of CA certificates to trust, see [TlsConfig::with_ca_file]
+    pub ca_file: Option<PathBuf>,
+    /// Only trust `ca_file`, see [TlsConfig::with_ca_only]
+    pub ca_only: Option<bool>,
+    /// PEM file holding the client certificate for mutual TLS
+    pub client_cert: Option<PathBuf>,
+    /// PEM file holding the client certificate's private key
+    pub client_key: Option<PathBuf>,
+    /// The name to expect on the server's certificate, see [TlsConfig::with_server_name]
+    pub server_name: Option<String>,
+}
+
+impl Profile {
+    /// Build a [ServerConfig] for the REST API or HEC, with the `SPLUNK_API_*`/`SPLUNK_HEC_*` environment variables
+    /// (`HOSTNAME`, `PORT`, `TOKEN`, `USERNAME` and `PASSWORD`) taking priority over the profile.
+    ///
+    /// For the REST API a token is used over a username and password.
+    pub fn server_config(&self, configtype: ServerConfigType) -> Result<ServerConfig, SplunkError> {
+        self.server_config_with_env(configtype, |name| env::var(name).ok())
+    }
+
+    /// [Profile::server_config], looking up environment variables with `env`
+    pub(crate) fn server_config_with_env(
+        &self,
+        configtype: ServerConfigType,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<ServerConfig, SplunkError> {
+        let env_prefix = configtype.env_prefix();
+        let from_env = |name: &str| env(&format!("{env_prefix}{name}"));
+
+        let hostname = from_env("HOSTNAME")
+            .or_else(|| self.hostname.clone())
+            .ok_or_else(|| {
+                SplunkError::Generic(format!(
+                    "The profile doesn't set a hostname and {env_prefix}HOSTNAME isn't set"
+                ))
+            })?;
+        let port = match from_env("PORT") {
+            Some(port) => port.parse::<u16>()?,
+            None => match configtype {
+                ServerConfigType::Hec => self.hec_port.unwrap_or(8088),
+                ServerConfigType::Api => self.port.unwrap_or(8089),
+            },
+        };
+
+        let mut config = ServerConfig::new(hostname)
+            .with_port(port)
+            .use_tls(self.scheme.unwrap_or_default() == Scheme::Https)
+            .with_verify_tls(self.verify_tls.unwrap_or(true))
+            .with_namespace(self.owner.as_deref(), self.app.as_deref());
+        if let Some(base_path) = &self.base_path {
+            config = config.with_base_path(base_path);
+        }
+        if let Some(tls) = self.tls_config()? {
+            config = config.with_tls_config(tls);
+        }
+
+        match configtype {
+            ServerConfigType::Hec => {
+                let token = match from_env("TOKEN") {
+                    Some(token) => Some(Secret::from(token)),
+                    None => read_secret(
+                        &self.hec_token,
+                        &self.hec_token_file,
+                        &self.hec_token_command,
+                    )?,
+                };
+                let token = token.ok_or_else(|| {
+                    SplunkError::Generic(format!(
+                        "The profile doesn't set a HEC token and {env_prefix}TOKEN isn't set"
+                    ))
+                })?;
+                Ok(config.with_token(token))
+            }
+            ServerConfigType::Api => {
+                let token = match from_env("TOKEN") {
+                    Some(token) => Some(Secret::from(token)),
+                    None => read_secret(&self.token, &self.token_file, &self.token_command)?,
+                };
+                if let Some(token) = token {
+                    return Ok(config.with_token(token));
+                }
+                // the unprefixed names are what older versions read
+                let username = from_env("USERNAME")
+                    .or_else(|| env("SPLUNK_USERNAME"))
+                    .or_else(|| self.username.clone());
+                let password = match from_env("PASSWORD").or_else(|| env("SPLUNK_PASSWORD")) {
+                    Some(password) => Some(Secret::from(password)),
+                    None => {
+                        read_secret(&self.password, &self.password_file, &self.password_command)?
+                    }
+                };
+                match (username, password) {
+                    (Some(username), Some(password)) => {
+                        Ok(config.with_username_password(username, password))
+                    }
+                    _ => Err(SplunkError::NoAuthMethodSelected),
+                }
+            }
+        }
+    }
+
+    /// The TLS settings, if the profile has any
+    fn tls_config(&self) -> Result<Option<TlsConfig>, SplunkError> {
+        if self.ca_file.is_none()
+            && self.ca_only.is_none()
+            && self.client_cert.is_none()
+            && self.client_key.is_none()
+            && self.server_name.is_none()
+        {
+            return Ok(None);
+        }
+        let mut tls = TlsConfig::default().with_ca_only(self.ca_only.unwrap_or(false));
+        if let Some(ca_file) = &self.ca_file {
+            tls = tls.with_ca_file(expand_home(ca_file))?;
+        }
+        match (&self.client_cert, &self.client_key) {
+            (Some(certificate), Some(key)) => {
+                tls = tls.with_client_identity_files(expand_home(certificate), expand_home(key))?;
+            }
+            (None, None) => {}
+            _ => {
+                return Err(SplunkError::Generic(
+                    "The profile needs both client_cert and client_key for mutual TLS".to_string(),
+                ))
+            }
+        }
+        if let Some(server_name) = &self.server_name {
+            tls = tls.with_server_name(server_name);
+        }
+        Ok(Some(tls))
+    }
+}
+
+/// A secret given inline, in a file or by a command
+fn read_secret(
+    value: &Option<Secret>,
+    file: &Option<PathBuf>,
+    command: &Option<Vec<String>>,
+) -> Result<Option<Secret>, SplunkError> {
+    if let Some(value) = value {
+        return Ok(Some(value.clone()));
+    }
+    if let Some(file) = file {
+        return Secret::from_file(expand_home(file)).map(Some);
+    }
+    match command.as_deref() {
+        Some([program, args @ ..]) => {
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            Secret::from_command(program, &args).map(Some)
+        }
+        Some([]) => Err(SplunkError::Generic(
+            "The profile has an empty credential command".to_string(),
+        )),
+        None => Ok(None),
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+/// Expand a leading `~/` to the home directory
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
@@ -0,0 +1,234 @@
+//! Retry and backoff handling, shared by [crate::hec::HecClient] and [crate::client::SplunkClient]
+//!
+//! Both clients default to [RetryPolicy::none], so nothing is retried unless you ask for it. REST requests which might
+//! create something twice (a `POST` or `PATCH`) are only retried when they couldn't connect, unless they're marked
+//! with [crate::client::RestRequest::idempotent].
+//!
+//! ```
+//! use std::time::Duration;
+//! use splunk::hec::HecClient;
+//! use splunk::retry::RetryPolicy;
+//!
+//! let client = HecClient::new("token", "localhost").with_retry_policy(
+//!     RetryPolicy::default()
+//!         .with_max_attempts(10)
+//!         .with_max_backoff(Duration::from_secs(60)),
+//! );
+//! ```
+
+use std::future::Future;
+use std::time::{Duration, SystemTime};
+
+use log::debug;
+use reqwest::header::RETRY_AFTER;
+use reqwest::Response;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::SplunkError;
+
+/// The status codes retried by [RetryPolicy::default] - too many requests, bad gateway, server busy and gateway timeout.
+///
+/// HEC returns a 503 along with code 9 ("Server is busy") when the indexers are applying back pressure.
+pub const DEFAULT_RETRYABLE_STATUS_CODES: [u16; 4] = [429, 502, 503, 504];
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+/// How (and whether) to retry failed requests, using exponential backoff with jitter
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one - `1` disables retries
+    pub max_attempts: u32,
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+    /// Upper bound on the delay between attempts
+    pub max_backoff: Duration,
+    /// The delay is multiplied by this after each attempt
+    pub multiplier: f64,
+    /// Randomly adds up to this fraction (`0.0` - `1.0`) of the delay, so clients don't retry in lockstep
+    pub jitter: f64,
+    /// HTTP status codes which will be retried
+    pub retryable_status_codes: Vec<u16>,
+    /// Retry when we can't connect to the server or the request times out
+    pub retry_connection_errors: bool,
+    /// Wait for as long as the `Retry-After` header says (a number of seconds or an HTTP date) instead of the calculated
+    /// backoff, when the server sends one
+    pub honour_retry_after: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.2,
+            retryable_status_codes: DEFAULT_RETRYABLE_STATUS_CODES.to_vec(),
+            retry_connection_errors: true,
+            honour_retry_after: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that makes a single attempt and never retries
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Set the total number of attempts, including the first one
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Set the delay before the first retry
+    pub fn with_initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Set the upper bound on the delay between attempts
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Set the backoff multiplier
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Set the jitter fraction, clamped to `0.0` - `1.0`
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Replace the list of retryable HTTP status codes
+    pub fn with_retryable_status_codes(mut self, status_codes: Vec<u16>) -> Self {
+        self.retryable_status_codes = status_codes;
+        self
+    }
+
+    /// Is this HTTP status code one we'll retry?
+    pub fn is_retryable_status(&self, status: u16) -> bool {
+        self.retryable_status_codes.contains(&status)
+    }
+
+    /// Is this `reqwest` error one we'll retry?
+    pub fn is_retryable_error(&self, error: &reqwest::Error) -> bool {
+        self.retry_connection_errors && (error.is_connect() || error.is_timeout())
+    }
+
+    /// The delay before the given retry (1 is the first retry), without jitter
+    pub fn base_backoff(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay = self.initial_backoff.as_secs_f64() * self.multiplier.max(1.0).powi(exponent);
+        if !delay.is_finite() || delay >= self.max_backoff.as_secs_f64() {
+            self.max_backoff
+        } else {
+            Duration::from_secs_f64(delay)
+        }
+    }
+
+    /// The delay before the given retry (1 is the first retry), including jitter and capped at `max_backoff`
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let base = self.base_backoff(retry);
+        let jitter = base.mul_f64(self.jitter.clamp(0.0, 1.0) * rand::random::<f64>());
+        (base + jitter).min(self.max_backoff)
+    }
+
+    /// Work out how long to wait before retrying this response, or `None` if it shouldn't be retried
+    fn response_delay(&self, response: &Response, retry: u32) -> Option<Duration> {
+        if !self.is_retryable_status(response.status().as_u16()) {
+            return None;
+        }
+        if self.honour_retry_after {
+            if let Some(delay) = retry_after(response, SystemTime::now()) {
+                return Some(delay);
+            }
+        }
+        Some(self.backoff(retry))
+    }
+
+    /// Runs `send` until it returns something we shouldn't retry, or we run out of attempts.
+    ///
+    /// A retryable status on the final attempt is returned as-is, so callers still get to check the status.
+    pub(crate) async fn execute<F, Fut>(&self, send: F) -> Result<Response, SplunkError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<Response, reqwest::Error>>,
+    {
+        self.run(true, send).await
+    }
+
+    /// Like [RetryPolicy::execute], for a request that mustn't be sent twice - it's only retried when it couldn't
+    /// connect, so the server never saw it. Timeouts and retryable statuses are returned as they are.
+    pub(crate) async fn execute_non_idempotent<F, Fut>(
+        &self,
+        send: F,
+    ) -> Result<Response, SplunkError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<Response, reqwest::Error>>,
+    {
+        self.run(false, send).await
+    }
+
+    async fn run<F, Fut>(&self, idempotent: bool, mut send: F) -> Result<Response, SplunkError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<Response, reqwest::Error>>,
+    {
+        let mut attempt: u32 = 1;
+        loop {
+            let result = send().await;
+            if attempt >= self.max_attempts {
+                return result.map_err(SplunkError::from);
+            }
+            let delay = match &result {
+                Ok(response) if idempotent => self.response_delay(response, attempt),
+                Ok(_) => None,
+                // a connect error (including a connect timeout) means nothing was sent
+                Err(error)
+                    if self.is_retryable_error(error) && (idempotent || error.is_connect()) =>
+                {
+                    Some(self.backoff(attempt))
+                }
+                Err(_) => None,
+            };
+            let Some(delay) = delay else {
+                return result.map_err(SplunkError::from);
+            };
+            match &result {
+                Ok(response) => debug!(
+                    "Attempt {}/{} got status {}, retrying in {:?}",
+                    attempt,
+                    self.max_attempts,
+                    response.status(),
+                    delay
+                ),
+                Err(error) => debug!(
+                    "Attempt {}/{} failed ({}), retrying in {:?}",
+                    attempt, self.max_attempts, error, delay
+                ),
+            }
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Parse the `Retry-After` header, which is either a number of seconds or an HTTP date. A date in the past means
+/// retry straight away.
+fn retry_after(response: &Response, now: SystemTime) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or_default())
+}
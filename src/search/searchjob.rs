@@ -5,6 +5,7 @@
 use futures_util::TryStreamExt;
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::time::Duration;
 use tokio::io::AsyncBufReadExt;
 use tracing::debug;
 // for map_err
@@ -123,6 +124,8 @@ pub struct SearchJobBuilder {
     /// If you want to specify extra search options - see the details under `POST` in <https://docs.splunk.com/Documentation/Splunk/9.0.4/RESTREF/RESTsearch#search.2Fjobs>
     extra_options: HashMap<String, String>,
     timeout: u32,
+    /// How long the export request can take, see [SearchJobBuilder::request_timeout]
+    request_timeout: Option<Duration>,
 }
 
 impl Default for SearchJobBuilder {
@@ -147,6 +150,7 @@ impl Default for SearchJobBuilder {
             id: None,
             extra_options: default_extra_options,
             timeout: 86400,
+            request_timeout: None,
         }
     }
 }
@@ -232,7 +236,10 @@ impl SearchJobBuilder {
 
         debug!("Payload: {:?}", payload);
 
-        let creation_response = match client.do_post(endpoint, payload).await {
+        let creation_response = match client
+            .do_post_with_timeout(endpoint, payload, self.request_timeout)
+            .await
+        {
             Err(err) => return Err(SplunkError::SearchCreationFailed(format!("{:?}", err))),
             Ok(val) => val,
         };
@@ -279,6 +286,17 @@ impl SearchJobBuilder {
         }
     }
 
+    /// Give up on the export after this long, including streaming the results.
+    ///
+    /// Exports stream results for as long as the search runs, so by default they ignore the total timeout in
+    /// [crate::ServerConfig::timeouts] - the read timeout still catches a stalled stream.
+    pub fn request_timeout(self, request_timeout: Duration) -> Self {
+        Self {
+            request_timeout: Some(request_timeout),
+            ..self
+        }
+    }
+
     /// Set the earliest time on the search job
     pub fn earliest_time(self, earliest_time: impl Into<String>) -> Self {
         Self {
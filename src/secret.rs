@@ -0,0 +1,125 @@
+//! Passwords, tokens and session keys which shouldn't end up in logs
+//!
+//! A [Secret] shows up as `<redacted>` in `Debug` and `Display` output, and its memory is zeroed when it's dropped.
+//! It doesn't implement `Serialize`, so credentials are left out when a [crate::ServerConfig] or
+//! [crate::client::SplunkClient] is serialized - use [expose] with `serialize_with` to opt in.
+//!
+//! ```no_run
+//! use splunk::secret::Secret;
+//! use splunk::ServerConfig;
+//! # fn example() -> Result<(), splunk::errors::SplunkError> {
+//! let token = Secret::from_command("pass", &["show", "splunk/prod-token"])?;
+//! let serverconfig = ServerConfig::new("splunk.example.com".to_string()).with_token(token);
+//! println!("{serverconfig:?}"); // the token's redacted
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::{Debug, Display};
+use std::path::Path;
+use std::process::Command;
+
+use reqwest::header::HeaderValue;
+use serde::{Deserialize, Deserializer, Serializer};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::errors::SplunkError;
+
+const REDACTED: &str = "<redacted>";
+
+#[derive(Clone, Default, PartialEq, Eq)]
+/// A credential that's redacted when printed and zeroed when dropped, see the [module documentation](self)
+pub struct Secret(String);
+
+impl Secret {
+    /// Wrap a credential
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    /// Read a credential from a file, trimming surrounding whitespace (like the trailing newline)
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, SplunkError> {
+        let path = path.as_ref();
+        let contents = Zeroizing::new(std::fs::read_to_string(path).map_err(|err| {
+            SplunkError::Generic(format!("Failed to read {}: {}", path.display(), err))
+        })?);
+        Ok(Self::new(contents.trim()))
+    }
+
+    /// Get a credential from the output of a command, like a password manager's CLI, trimming surrounding whitespace
+    pub fn from_command(program: &str, args: &[&str]) -> Result<Self, SplunkError> {
+        let output = Command::new(program)
+            .args(args)
+            .output()
+            .map_err(|err| SplunkError::Generic(format!("Failed to run {program:?}: {err}")))?;
+        let stdout = Zeroizing::new(output.stdout);
+        if !output.status.success() {
+            return Err(SplunkError::Generic(format!(
+                "{program:?} failed ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        let secret = std::str::from_utf8(&stdout)
+            .map_err(|_| SplunkError::Generic(format!("{program:?} didn't print valid UTF-8")))?;
+        Ok(Self::new(secret.trim()))
+    }
+
+    /// The credential itself, for putting in a request
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// An `Authorization` header value like `Splunk <token>`, marked sensitive so reqwest won't log it
+    pub(crate) fn auth_header(&self, scheme: &str) -> Result<HeaderValue, SplunkError> {
+        let mut value = HeaderValue::try_from(format!("{scheme} {}", self.expose()))?;
+        value.set_sensitive(true);
+        Ok(value)
+    }
+
+    /// Is it empty?
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl Display for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl From<String> for Secret {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(secret: &str) -> Self {
+        Self::new(secret)
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self)
+    }
+}
+
+/// Serialize the credential itself, for `#[serde(serialize_with = "splunk::secret::expose")]` when you really mean it
+pub fn expose<S: Serializer>(secret: &Secret, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(secret.expose())
+}
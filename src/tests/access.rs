@@ -0,0 +1,193 @@
+use crate::client::{Role, User};
+use crate::errors::SplunkError;
+use crate::secret::Secret;
+use crate::tests::client::mock_client;
+use crate::tests::mock_server;
+use crate::ServerConfig;
+
+const ROLES: &str = r#"{"entry":[
+    {"name":"analyst","content":{
+        "capabilities":["search","rtsearch","schedule_search"],"imported_roles":["user"],
+        "imported_capabilities":["get_metadata"],
+        "srchIndexesAllowed":["web*","main"],"srchIndexesDefault":["main"],
+        "srchJobsQuota":10,"rtSrchJobsQuota":"6","srchDiskQuota":500,"cumulativeSrchJobsQuota":0,
+        "srchTimeWin":-1,"srchFilter":"","defaultApp":"search"}},
+    {"name":"user","content":{"capabilities":["search"],"srchJobsQuota":"","srchFilter":"index=main"}}
+]}"#;
+
+#[test]
+async fn test_roles() -> Result<(), SplunkError> {
+    let (port, server) = mock_server(vec![
+        (200, ROLES),
+        (
+            404,
+            r#"{"messages":[{"type":"ERROR","text":"Could not find object id=auditor"}]}"#,
+        ),
+        (201, "{}"),
+        (200, "{}"),
+        (200, "{}"),
+    ])?;
+    let client = mock_client(
+        port,
+        ServerConfig::new("127.0.0.1".to_string()).with_token("token"),
+    )?;
+
+    let roles = client.list_roles().await?;
+    assert_eq!(roles.len(), 2);
+    let analyst = &roles[0];
+    assert_eq!(analyst.name, "analyst");
+    assert_eq!(
+        analyst.capabilities,
+        vec!["rtsearch", "schedule_search", "search"]
+    );
+    assert_eq!(analyst.imported_roles, vec!["user"]);
+    assert_eq!(analyst.srch_indexes_allowed, vec!["main", "web*"]);
+    assert_eq!(analyst.srch_jobs_quota, Some(10));
+    assert_eq!(analyst.rt_srch_jobs_quota, Some(6));
+    assert_eq!(analyst.srch_time_win, Some(-1));
+    assert_eq!(analyst.srch_filter, None);
+    assert_eq!(analyst.default_app.as_deref(), Some("search"));
+    assert_eq!(roles[1].srch_jobs_quota, None);
+    assert_eq!(roles[1].srch_filter.as_deref(), Some("index=main"));
+
+    // a source-of-truth file in a different order is the same role
+    let wanted: Role = serde_json::from_str(
+        r#"{"name":"analyst","capabilities":["search","schedule_search","rtsearch"],"imported_roles":["user"],
+            "srchIndexesAllowed":["web*","main"],"srchIndexesDefault":["main"],"srchJobsQuota":10,
+            "rtSrchJobsQuota":6,"srchDiskQuota":500,"cumulativeSrchJobsQuota":0,"srchTimeWin":-1,
+            "defaultApp":"search"}"#,
+    )?;
+    assert_ne!(&wanted, analyst);
+    assert_eq!(&wanted.normalized(), analyst);
+    let round_trip: Role = serde_json::from_str(&serde_json::to_string(analyst)?)?;
+    assert_eq!(&round_trip, analyst);
+
+    // a file that leaves settings out still matches, even though the server's filled them in
+    let mut partial = Role::new("analyst");
+    partial.capabilities = vec![
+        "search".to_string(),
+        "schedule_search".to_string(),
+        "rtsearch".to_string(),
+    ];
+    partial.imported_roles = vec!["user".to_string()];
+    partial.srch_indexes_allowed = vec!["web*".to_string(), "main".to_string()];
+    partial.srch_indexes_default = vec!["main".to_string()];
+    assert_ne!(&partial.clone().normalized(), analyst);
+    assert!(partial.differences_from(analyst).is_empty());
+    partial.capabilities.pop();
+    partial.srch_jobs_quota = Some(20);
+    assert_eq!(
+        partial.differences_from(analyst),
+        vec!["capabilities", "srchJobsQuota"]
+    );
+
+    assert_eq!(client.get_role("auditor").await?, None);
+    let mut auditor = Role::new("auditor");
+    auditor.imported_roles = vec!["user".to_string()];
+    auditor.srch_indexes_allowed = vec!["_audit".to_string(), "main".to_string()];
+    auditor.srch_jobs_quota = Some(3);
+    client.create_role(&auditor).await?;
+    client.update_role(&Role::new("auditor")).await?;
+    client.delete_role("auditor").await?;
+
+    let requests = server
+        .join()
+        .map_err(|_| SplunkError::Generic("mock server failed".to_string()))?;
+    assert_eq!(
+        requests[0].0,
+        "GET /services/authorization/roles?count=0&output_mode=json HTTP/1.1"
+    );
+    assert_eq!(
+        requests[1].0,
+        "GET /services/authorization/roles/auditor?output_mode=json HTTP/1.1"
+    );
+    assert_eq!(requests[2].0, "POST /services/authorization/roles HTTP/1.1");
+    assert_eq!(
+        requests[2].1,
+        "name=auditor&capabilities=&imported_roles=user&srchIndexesAllowed=_audit&srchIndexesAllowed=main&srchIndexesDefault=&srchJobsQuota=3"
+    );
+    // lists are always sent, so they're cleared
+    assert_eq!(
+        requests[3].0,
+        "POST /services/authorization/roles/auditor HTTP/1.1"
+    );
+    assert_eq!(
+        requests[3].1,
+        "capabilities=&imported_roles=&srchIndexesAllowed=&srchIndexesDefault="
+    );
+    assert_eq!(
+        requests[4].0,
+        "DELETE /services/authorization/roles/auditor HTTP/1.1"
+    );
+    Ok(())
+}
+
+#[test]
+async fn test_users() -> Result<(), SplunkError> {
+    let (port, server) = mock_server(vec![
+        (
+            200,
+            r#"{"entry":[{"name":"jbloggs","content":{"realname":"Joe Bloggs","email":"jbloggs@example.com",
+                "roles":["user","analyst","user"],"defaultApp":"search","tz":"","type":"Splunk","locked-out":false}}]}"#,
+        ),
+        (201, "{}"),
+        (200, "{}"),
+        (200, "{}"),
+        (200, "{}"),
+    ])?;
+    let client = mock_client(
+        port,
+        ServerConfig::new("127.0.0.1".to_string()).with_token("token"),
+    )?;
+
+    let user = client
+        .get_user("jbloggs")
+        .await?
+        .ok_or_else(|| SplunkError::Generic("no user".to_string()))?;
+    assert_eq!(user.name, "jbloggs");
+    assert_eq!(user.realname.as_deref(), Some("Joe Bloggs"));
+    assert_eq!(user.roles, vec!["analyst", "user"]);
+    assert_eq!(user.tz, None);
+    assert_eq!(user.user_type, "Splunk");
+    assert!(!user.locked_out);
+    // things the server sets aren't written out
+    let serialized = serde_json::to_string(&user)?;
+    assert!(!serialized.contains("Splunk"), "{serialized}");
+
+    let mut new_user = User::new("svc ingest", &["user"]);
+    new_user.realname = Some("Ingest".to_string());
+    client
+        .create_user(&new_user, &Secret::new("s3cret&more"))
+        .await?;
+    client
+        .update_user(&User::new("svc ingest", &["user", "analyst"]))
+        .await?;
+    client
+        .set_user_password("svc ingest", &Secret::new("n3w"), Some(&Secret::new("old")))
+        .await?;
+    client.delete_user("svc ingest").await?;
+
+    let requests = server
+        .join()
+        .map_err(|_| SplunkError::Generic("mock server failed".to_string()))?;
+    assert_eq!(
+        requests[0].0,
+        "GET /services/authentication/users/jbloggs?output_mode=json HTTP/1.1"
+    );
+    assert_eq!(
+        requests[1].1,
+        "name=svc+ingest&password=s3cret%26more&realname=Ingest&roles=user"
+    );
+    assert_eq!(
+        requests[2].0,
+        "POST /services/authentication/users/svc%20ingest HTTP/1.1"
+    );
+    // the name and email aren't set, so they're left alone
+    assert_eq!(requests[2].1, "roles=user&roles=analyst");
+    assert_eq!(requests[3].1, "password=n3w&oldpassword=old");
+    assert_eq!(
+        requests[4].0,
+        "DELETE /services/authentication/users/svc%20ingest HTTP/1.1"
+    );
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use crate::errors::SplunkError;
+use crate::tests::client::mock_client;
+use crate::tests::mock_server;
+use crate::ServerConfig;
+
+const CURRENT_CONTEXT: &str = r#"{"links":{},"origin":"https://localhost:8089/services/authentication/current-context","entry":[{
+    "name":"context","content":{
+        "username":"jbloggs","realname":"Joe Bloggs","email":"jbloggs@example.com",
+        "roles":["power","user"],"defaultApp":"search","defaultAppIsUserOverride":false,
+        "capabilities":["rtsearch","schedule_search","search"],
+        "tz":"","lockedOut":false,"search_use_sid":true}}]}"#;
+
+const CAPABILITIES: &str = r#"{"entry":[{"name":"capabilities","content":{
+    "capabilities":["admin_all_objects","edit_indexes","rtsearch","search"]}}]}"#;
+
+#[test]
+async fn test_current_context_and_capabilities() -> Result<(), SplunkError> {
+    let (port, server) = mock_server(vec![
+        (200, CURRENT_CONTEXT),
+        (200, CAPABILITIES),
+        (200, CURRENT_CONTEXT),
+    ])?;
+    let client = mock_client(
+        port,
+        ServerConfig::new("127.0.0.1".to_string()).with_token("token"),
+    )?;
+
+    let context = client.get_current_context().await?;
+    assert_eq!(context.username, "jbloggs");
+    assert_eq!(context.realname, "Joe Bloggs");
+    assert_eq!(context.default_app, "search");
+    assert_eq!(context.tz, None);
+    assert!(!context.locked_out);
+    assert!(context.has_role("power"));
+    assert!(context.has_capability("rtsearch"));
+    assert!(!context.has_capability("edit_indexes"));
+
+    // the server has it, but that doesn't mean the user does
+    let capabilities = client.get_capabilities().await?;
+    assert_eq!(capabilities.capabilities.len(), 4);
+    assert!(capabilities.has_capability("edit_indexes"));
+    assert!(!client.has_capability("edit_indexes").await?);
+
+    let requests = server
+        .join()
+        .map_err(|_| SplunkError::Generic("mock server failed".to_string()))?;
+    assert_eq!(
+        requests[0].0,
+        "GET /services/authentication/current-context?output_mode=json HTTP/1.1"
+    );
+    assert_eq!(
+        requests[1].0,
+        "GET /services/authorization/capabilities?output_mode=json HTTP/1.1"
+    );
+    Ok(())
+}
+
+#[test]
+async fn test_current_context_with_time_zone() -> Result<(), SplunkError> {
+    let (port, _server) = mock_server(vec![
+        (
+            200,
+            r#"{"entry":[{"name":"context","content":{"username":"admin","tz":"Australia/Brisbane","lockedOut":"1"}}]}"#,
+        ),
+        (200, r#"{"entry":[]}"#),
+    ])?;
+    let client = mock_client(
+        port,
+        ServerConfig::new("127.0.0.1".to_string()).with_token("token"),
+    )?;
+
+    let context = client.get_current_context().await?;
+    assert_eq!(context.tz.as_deref(), Some("Australia/Brisbane"));
+    assert!(context.locked_out);
+    assert!(context.roles.is_empty());
+
+    // no entries is an error, not an empty context
+    assert!(client.get_current_context().await.is_err());
+    Ok(())
+}
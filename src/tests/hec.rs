@@ -3,6 +3,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use serde_json::{json, Value};
 
 use crate::errors::SplunkError;
+use crate::tests::mock_server;
 
 #[tokio::test]
 #[cfg_attr(feature = "test_ci", ignore)]
@@ -50,7 +51,9 @@ async fn send_test_data() -> Result<(), SplunkError> {
         "test" :1, "_time" : unix_time, "message" : "Hello from splunk-rs testing",
     });
 
-    client.send_event(test_event).await
+    let response = client.send_event(test_event).await?;
+    assert!(response.is_success());
+    Ok(())
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -95,7 +98,7 @@ async fn send_queued_multi_overized_batch() -> Result<(), SplunkError> {
 
     for i in 0..3 {
         let event = TestEvent::new("send_queued_multi", &format!("Event {:?}", i));
-        client.enqueue(event).await;
+        client.enqueue(event).await?;
     }
 
     client.flush(Some(20)).await?;
@@ -116,9 +119,910 @@ async fn send_with_custom_useragent() -> Result<(), SplunkError> {
 
     for i in 0..3 {
         let event = TestEvent::new("send_with_custom_useragent", &format!("Event {:?}", i));
-        client.enqueue(event).await;
+        client.enqueue(event).await?;
     }
     client.flush(Some(20)).await?;
 
     Ok(())
 }
+
+#[test]
+async fn test_hec_indexer_ack_sets_channel() {
+    use crate::hec::{AckOptions, HecClient};
+
+    let client = HecClient::new("token", "localhost");
+    assert!(client.channel().is_none());
+
+    let client = client.with_indexer_ack(AckOptions::default());
+    let channel = client.channel().expect("channel should be generated");
+    assert!(uuid::Uuid::parse_str(channel).is_ok());
+
+    let client = HecClient::new("token", "localhost")
+        .with_channel("11111111-2222-3333-4444-555555555555")
+        .with_indexer_ack(AckOptions::default());
+    assert_eq!(
+        client.channel(),
+        Some("11111111-2222-3333-4444-555555555555")
+    );
+}
+
+#[cfg_attr(feature = "test_ci", ignore)]
+#[tokio::test]
+async fn send_acknowledged_batch() -> Result<(), SplunkError> {
+    use crate::hec::{AckOptions, HecClient};
+    use crate::{ServerConfig, ServerConfigType};
+
+    let client = HecClient::with_serverconfig(ServerConfig::try_from_env(ServerConfigType::Hec)?)
+        .with_random_channel();
+
+    let events: Vec<TestEvent> = (0..3)
+        .map(|i| TestEvent::new("send_acknowledged_batch", &format!("Event {:?}", i)))
+        .collect();
+    let ack_id = client
+        .send_events_acknowledged(events, &AckOptions::default())
+        .await?;
+    eprintln!("ackId: {ack_id}");
+    Ok(())
+}
+
+#[test]
+async fn test_hec_raw_endpoint_params() {
+    use crate::hec::HecClient;
+
+    let client = HecClient::new("token", "localhost");
+    assert_eq!(client.raw_endpoint(), "/services/collector/raw");
+
+    let client = client.with_sourcetype("syslog").with_host("my host");
+    assert_eq!(
+        client.raw_endpoint(),
+        "/services/collector/raw?sourcetype=syslog&host=my%20host"
+    );
+    let client = client
+        .with_channel("FE0ECFAD-13D5-401B-847D-77833BD77131")
+        .with_index("main");
+    assert_eq!(
+        client.raw_endpoint(),
+        "/services/collector/raw?channel=FE0ECFAD-13D5-401B-847D-77833BD77131&index=main&sourcetype=syslog&host=my%20host"
+    );
+}
+
+#[cfg_attr(feature = "test_ci", ignore)]
+#[tokio::test]
+async fn send_raw_lines() -> Result<(), SplunkError> {
+    use crate::hec::HecClient;
+    use crate::{ServerConfig, ServerConfigType};
+
+    let client = HecClient::with_serverconfig(ServerConfig::try_from_env(ServerConfigType::Hec)?)
+        .with_random_channel();
+
+    client
+        .send_raw_lines(&[
+            "send_raw_lines line one from splunk-rs",
+            "send_raw_lines line two from splunk-rs",
+        ])
+        .await?;
+    Ok(())
+}
+
+#[test]
+async fn test_hec_event_payload() -> Result<(), SplunkError> {
+    use crate::hec::{HecClient, HecEvent, IntoHecEvent};
+    use std::time::Duration;
+
+    let client = HecClient::new("token", "localhost")
+        .with_index("main")
+        .with_sourcetype("client:sourcetype");
+
+    // plain serializable things just get wrapped up with the client defaults
+    let plain: Value = serde_json::from_str(&"hello".into_hec_event()?.to_json(&client)?)?;
+    assert_eq!(
+        plain,
+        json!({"event": "hello", "index": "main", "sourcetype": "client:sourcetype"})
+    );
+
+    let event = HecEvent::new(json!({"message": "hello"}))
+        .with_time(UNIX_EPOCH + Duration::from_millis(1_700_000_000_123))
+        .with_host("web01")
+        .with_sourcetype("event:sourcetype")
+        .with_field("environment", "production");
+    let payload: Value = serde_json::from_str(&event.into_hec_event()?.to_json(&client)?)?;
+    assert_eq!(
+        payload,
+        json!({
+            "time": 1_700_000_000.123,
+            "host": "web01",
+            "index": "main",
+            "sourcetype": "event:sourcetype",
+            "event": {"message": "hello"},
+            "fields": {"environment": "production"},
+        })
+    );
+    Ok(())
+}
+
+#[test]
+async fn test_metric_event_formats() -> Result<(), SplunkError> {
+    use crate::hec::{HecClient, IntoHecEvent, MetricEvent};
+
+    let client = HecClient::new("token", "localhost").with_index("metrics");
+
+    let single = MetricEvent::new()
+        .with_measurement("cpu.idle", 95.5)
+        .with_dimension("region", "us-west-1")
+        .into_hec_event()?;
+    let single: Value = serde_json::from_str(&single.to_json(&client)?)?;
+    assert_eq!(
+        single,
+        json!({
+            "index": "metrics",
+            "event": "metric",
+            "fields": {"metric_name": "cpu.idle", "_value": 95.5, "region": "us-west-1"},
+        })
+    );
+
+    let multi = MetricEvent::new()
+        .with_measurement("cpu.idle", 95.5)
+        .with_measurement("cpu.user", 3.0)
+        .into_hec_event()?;
+    let multi: Value = serde_json::from_str(&multi.to_json(&client)?)?;
+    assert_eq!(
+        multi["fields"],
+        json!({"metric_name:cpu.idle": 95.5, "metric_name:cpu.user": 3.0})
+    );
+
+    assert!(MetricEvent::new().into_hec_event().is_err());
+    assert!(MetricEvent::new()
+        .with_measurement("cpu.idle", f64::NAN)
+        .validate()
+        .is_err());
+    assert!(MetricEvent::new()
+        .with_measurement("cpu.idle", 1.0)
+        .with_dimension("metric_name", "nope")
+        .validate()
+        .is_err());
+    Ok(())
+}
+
+#[test]
+async fn test_metrics_recorder_aggregates() -> Result<(), SplunkError> {
+    use crate::hec::{HecClient, MetricsRecorder};
+
+    let recorder = MetricsRecorder::new(HecClient::new("token", "localhost"));
+    assert!(recorder.is_empty().await);
+
+    recorder
+        .gauge("queue.depth", 1.0, &[("queue", "a")])
+        .await?;
+    recorder
+        .gauge("queue.depth", 2.0, &[("queue", "a")])
+        .await?;
+    recorder.counter("requests", 1.0, &[("queue", "a")]).await?;
+    recorder.counter("requests", 1.0, &[("queue", "b")]).await?;
+    assert_eq!(recorder.len().await, 2);
+
+    assert!(recorder.gauge("bad", f64::INFINITY, &[]).await.is_err());
+    assert_eq!(recorder.len().await, 2);
+
+    // the same name can't be a gauge and a counter in one event, but it can with other dimensions
+    recorder
+        .counter("queue.depth", 1.0, &[("queue", "a")])
+        .await
+        .expect_err("queue.depth is a gauge");
+    recorder
+        .gauge("requests", 1.0, &[("queue", "b")])
+        .await
+        .expect_err("requests is a counter");
+    recorder
+        .counter("queue.depth", 1.0, &[("queue", "c")])
+        .await?;
+    assert_eq!(recorder.len().await, 3);
+    Ok(())
+}
+
+#[test]
+async fn test_metrics_recorder_keeps_unsent_aggregates() -> Result<(), SplunkError> {
+    use crate::hec::{HecClient, MetricsRecorder};
+
+    // the first request's accepted, the second isn't, then the retry is
+    let (port, server) = mock_server(vec![
+        (200, r#"{"text":"Success","code":0}"#),
+        (503, r#"{"text":"Server is busy","code":9}"#),
+        (200, r#"{"text":"Success","code":0}"#),
+    ])?;
+    let mut client = HecClient::new("token", "127.0.0.1");
+    client.serverconfig = client.serverconfig.use_tls(false).with_port(port);
+    let recorder = MetricsRecorder::new(client).with_batch_size(1);
+
+    recorder.counter("requests", 1.0, &[("queue", "a")]).await?;
+    recorder.counter("requests", 1.0, &[("queue", "b")]).await?;
+    recorder.flush().await.expect_err("the server's busy");
+
+    // only the failed series is left, and it's still a counter so new counts are added to it
+    assert_eq!(recorder.len().await, 1);
+    recorder.counter("requests", 2.0, &[("queue", "b")]).await?;
+    assert_eq!(recorder.len().await, 1);
+    assert_eq!(recorder.flush().await?, 1);
+    assert!(recorder.is_empty().await);
+
+    let requests = server
+        .join()
+        .map_err(|_| SplunkError::Generic("mock server failed".to_string()))?;
+    assert_eq!(requests.len(), 3);
+    assert!(requests[0].1.contains(r#""queue":"a""#));
+    assert!(requests[2].1.contains(r#""queue":"b""#));
+    assert!(
+        requests[2].1.contains(r#""_value":3.0"#),
+        "{}",
+        requests[2].1
+    );
+    Ok(())
+}
+
+#[test]
+async fn test_hec_split_batches() {
+    use crate::hec::HecClient;
+
+    let client = HecClient::new("token", "localhost").with_max_payload_bytes(10);
+    assert_eq!(
+        client.split_batches(["aaaa", "bbbb", "cccc"], 100),
+        vec![("aaaa\nbbbb".to_string(), 2), ("cccc".to_string(), 1)]
+    );
+    assert_eq!(
+        client.split_batches(["aa", "bb", "cc"], 2),
+        vec![("aa\nbb".to_string(), 2), ("cc".to_string(), 1)]
+    );
+    assert!(client.split_batches(Vec::<String>::new(), 2).is_empty());
+}
+
+#[test]
+async fn test_hec_reports_partial_sends() -> Result<(), SplunkError> {
+    use crate::hec::HecClient;
+
+    let success = r#"{"text":"Success","code":0}"#;
+    let busy = r#"{"text":"Server is busy","code":9}"#;
+    let client = |port: u16| {
+        let mut client = HecClient::new("token", "127.0.0.1")
+            .with_random_channel()
+            .with_max_batch_events(1)
+            .with_max_payload_bytes(20);
+        client.serverconfig = client.serverconfig.clone().use_tls(false).with_port(port);
+        client
+    };
+
+    // the first of three batches is accepted, the second fails, and the third's never sent
+    let (port, server) = mock_server(vec![(200, success), (503, busy)])?;
+    let err = client(port)
+        .send_events(vec!["one", "two", "three"])
+        .await
+        .expect_err("the second batch failed");
+    let SplunkError::HecPartiallySent {
+        responses,
+        sent,
+        error,
+    } = err
+    else {
+        return Err(SplunkError::Generic(format!("wrong error: {err:?}")));
+    };
+    assert_eq!((responses.len(), sent), (1, 1));
+    assert!(matches!(*error, SplunkError::HecServerBusy(_)));
+    let requests = server
+        .join()
+        .map_err(|_| SplunkError::Generic("mock server failed".to_string()))?;
+    assert_eq!(requests.len(), 2);
+
+    let (port, _server) = mock_server(vec![(200, success), (503, busy)])?;
+    let err = client(port)
+        .send_raw_lines(&["first line", "second line", "third line"])
+        .await
+        .expect_err("the second batch failed");
+    assert!(
+        matches!(err, SplunkError::HecPartiallySent { sent: 1, .. }),
+        "{err:?}"
+    );
+
+    // nothing was accepted, so there's nothing to report
+    let (port, _server) = mock_server(vec![(503, busy)])?;
+    let err = client(port)
+        .send_events(vec!["one", "two"])
+        .await
+        .expect_err("the first batch failed");
+    assert!(matches!(err, SplunkError::HecServerBusy(_)), "{err:?}");
+    Ok(())
+}
+
+#[test]
+async fn test_hec_oversized_events() -> Result<(), SplunkError> {
+    use crate::hec::{HecClient, HecEvent, OversizedEventPolicy};
+
+    let event = HecEvent::new("x".repeat(100));
+
+    let client = HecClient::new("token", "localhost").with_max_payload_bytes(50);
+    assert!(matches!(
+        client.encode_event(&event),
+        Err(SplunkError::PayloadTooLarge {
+            size: 112,
+            limit: 50
+        })
+    ));
+
+    let client = client.with_oversized_event_policy(OversizedEventPolicy::Truncate);
+    let encoded = client.encode_event(&event)?;
+    assert_eq!(encoded.len(), 50);
+    assert_eq!(encoded, format!("{{\"event\":\"{}\"}}", "x".repeat(38)));
+
+    assert_eq!(client.encode_raw_line(&"é".repeat(30))?, "é".repeat(25));
+
+    // non-string events can't be truncated
+    assert!(client
+        .encode_event(&HecEvent::new(json!({"data": "x".repeat(100)})))
+        .is_err());
+    Ok(())
+}
+
+#[test]
+async fn test_hec_flush_drops_oversized_event() -> Result<(), SplunkError> {
+    use crate::hec::HecClient;
+
+    let mut client = HecClient::new("token", "localhost").with_max_payload_bytes(50);
+    client
+        .enqueue("x".repeat(100))
+        .await
+        .expect("Failed to enqueue");
+    let err = client
+        .flush(None)
+        .await
+        .expect_err("flush should have failed");
+    let SplunkError::FlushFailed(report) = err else {
+        return Err(err);
+    };
+    assert_eq!(report.dead_lettered.len(), 1);
+    assert!(matches!(
+        report.errors[0].error,
+        SplunkError::PayloadTooLarge { .. }
+    ));
+    assert_eq!(client.queue_size().await, 0);
+    Ok(())
+}
+
+#[test]
+async fn test_hec_gzip_compression() -> Result<(), SplunkError> {
+    use crate::hec::GzipOptions;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    let gzip = GzipOptions::default().with_threshold_bytes(100);
+    assert!(gzip.compress(b"too small to bother")?.is_none());
+
+    let body = "hello world ".repeat(100);
+    let compressed = gzip
+        .compress(body.as_bytes())?
+        .expect("body should have been compressed");
+    assert!(compressed.len() < body.len());
+
+    let mut decompressed = String::new();
+    GzDecoder::new(compressed.as_ref())
+        .read_to_string(&mut decompressed)
+        .expect("Failed to decompress");
+    assert_eq!(decompressed, body);
+    Ok(())
+}
+
+#[test]
+async fn test_hec_sender_overflow_and_shutdown() -> Result<(), SplunkError> {
+    use crate::hec::{HecClient, HecSender, HecSenderConfig, OverflowPolicy};
+    use std::time::Duration;
+
+    // nothing's listening on this port, so the final flush fails
+    let mut client = HecClient::new("token", "127.0.0.1");
+    client.serverconfig = client.serverconfig.with_port(1);
+    let sender = HecSender::spawn(
+        client,
+        HecSenderConfig::default()
+            .with_capacity(2)
+            .with_batch_size(100)
+            .with_linger(Duration::from_secs(3600))
+            .with_overflow_policy(OverflowPolicy::DropOldest),
+    );
+    for i in 0..3 {
+        sender.send(json!({ "event": i })).await?;
+    }
+    assert_eq!(sender.queue_size().await, 2);
+    assert_eq!(sender.stats().dropped, 1);
+
+    let stats = sender.shutdown().await?;
+    assert_eq!(stats.sent, 0);
+    assert_eq!(stats.failed, 2);
+    assert!(matches!(
+        sender.send("too late").await,
+        Err(SplunkError::HecSenderClosed)
+    ));
+    Ok(())
+}
+
+#[test]
+async fn test_hec_sender_stops_when_dropped() -> Result<(), SplunkError> {
+    use crate::hec::{HecClient, HecSender, HecSenderConfig};
+    use std::time::Duration;
+
+    let metrics = tokio::runtime::Handle::current().metrics();
+    let tasks = metrics.num_alive_tasks();
+    // nothing's listening on this port, so the final flush fails straight away
+    let mut client = HecClient::new("token", "127.0.0.1");
+    client.serverconfig = client.serverconfig.with_port(1);
+    let sender = HecSender::spawn(
+        client,
+        HecSenderConfig::default()
+            .with_batch_size(100)
+            .with_linger(Duration::from_secs(3600)),
+    );
+    sender.send("hello").await?;
+    assert_eq!(metrics.num_alive_tasks(), tasks + 1);
+
+    drop(sender);
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while metrics.num_alive_tasks() > tasks {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .map_err(|_| SplunkError::Generic("the sender task's still running".to_string()))?;
+    Ok(())
+}
+
+#[test]
+async fn test_hec_sender_overflows_while_retrying() -> Result<(), SplunkError> {
+    use crate::hec::{HecClient, HecSender, HecSenderConfig, OverflowPolicy};
+    use std::time::Duration;
+
+    // busy twice, then nothing's listening
+    let busy = r#"{"text":"Server is busy","code":9}"#;
+    let (port, server) = mock_server(vec![(503, busy), (503, busy)])?;
+    let mut client = HecClient::new("token", "127.0.0.1");
+    client.serverconfig = client.serverconfig.use_tls(false).with_port(port);
+    let sender = HecSender::spawn(
+        client,
+        HecSenderConfig::default()
+            .with_capacity(3)
+            .with_batch_size(1)
+            .with_linger(Duration::from_millis(50))
+            .with_overflow_policy(OverflowPolicy::DropNewest),
+    );
+    // slowly enough that the background task would have picked up each one if it wasn't retrying
+    for i in 0..6 {
+        sender.send(json!({ "event": i })).await?;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    // the failed event's waiting to be retried, so only two more fit
+    assert_eq!(sender.queue_size().await, 2);
+    assert_eq!(sender.stats().dropped, 3);
+
+    let requests = server
+        .join()
+        .map_err(|_| SplunkError::Generic("mock server failed".to_string()))?;
+    assert_eq!(requests[0].1, r#"{"event":{"event":0}}"#);
+    assert_eq!(requests[1].1, requests[0].1);
+
+    let stats = sender.shutdown().await?;
+    assert_eq!(stats.sent, 0);
+    assert_eq!(stats.failed, 3);
+    Ok(())
+}
+
+#[test]
+async fn test_hec_flush_requeues_failed_batch() -> Result<(), SplunkError> {
+    use crate::hec::HecClient;
+
+    // nothing's listening on this port, so the batch stays queued
+    let mut client = HecClient::new("token", "127.0.0.1");
+    client.serverconfig = client.serverconfig.with_port(1);
+    for i in 0..5 {
+        client.enqueue(json!({ "event": i })).await?;
+    }
+    let err = client
+        .flush(Some(2))
+        .await
+        .expect_err("flush should have failed");
+    let SplunkError::FlushFailed(report) = err else {
+        return Err(err);
+    };
+    assert_eq!(report.sent, 0);
+    assert_eq!(report.requeued, 2);
+    assert_eq!(report.failed, 2);
+    // we stop at the first batch that needs retrying
+    assert_eq!(report.errors.len(), 1);
+    assert!(report.dead_lettered.is_empty());
+    assert_eq!(client.queue_size().await, 5);
+    Ok(())
+}
+
+#[test]
+async fn test_hec_flush_failure_disposition() {
+    use crate::hec::{Disposition, HecResponse};
+
+    let hec_error = |status: u16, code: Option<u32>, invalid_event_number: Option<usize>| {
+        HecResponse {
+            text: "error".to_string(),
+            code,
+            ack_id: None,
+            invalid_event_number,
+        }
+        .into_error(status)
+    };
+    assert_eq!(
+        Disposition::from_error(&hec_error(400, Some(6), Some(3)), 10),
+        Disposition::InvalidEvent(3)
+    );
+    // a number outside the batch doesn't make sense, so the whole lot goes
+    assert_eq!(
+        Disposition::from_error(&hec_error(400, Some(6), Some(10)), 10),
+        Disposition::DeadLetter
+    );
+    assert_eq!(
+        Disposition::from_error(&hec_error(400, Some(12), None), 10),
+        Disposition::DeadLetter
+    );
+    assert_eq!(
+        Disposition::from_error(&hec_error(413, None, None), 10),
+        Disposition::DeadLetter
+    );
+    assert_eq!(
+        Disposition::from_error(&hec_error(503, Some(9), None), 10),
+        Disposition::Requeue
+    );
+    // these would never work, so they mustn't hold up the rest of the queue
+    for (status, code) in [(400, 7), (403, 4), (403, 1), (401, 2)] {
+        assert_eq!(
+            Disposition::from_error(&hec_error(status, Some(code), None), 10),
+            Disposition::DeadLetter,
+            "code {code}"
+        );
+    }
+    assert_eq!(
+        Disposition::from_error(&SplunkError::HecAckTimeout(1), 10),
+        Disposition::Requeue
+    );
+}
+
+#[test]
+async fn test_hec_response_errors() {
+    use crate::hec::HecResponse;
+
+    let response =
+        HecResponse::parse(r#"{"text":"Invalid data format","code":6,"invalid-event-number":2}"#);
+    assert!(!response.is_success());
+    assert_eq!(response.invalid_event_number, Some(2));
+    let error = response.into_error(400);
+    assert!(matches!(error, SplunkError::HecInvalidDataFormat(_)));
+    assert!(!error.is_retriable());
+    assert_eq!(
+        error.hec_response().and_then(|r| r.invalid_event_number),
+        Some(2)
+    );
+
+    let success = HecResponse::parse(r#"{"text":"Success","code":0,"ackId":7}"#);
+    assert!(success.is_success());
+    assert_eq!(success.ack_id, Some(7));
+
+    let busy = HecResponse::parse(r#"{"text":"Server is busy","code":9}"#).into_error(503);
+    assert!(matches!(busy, SplunkError::HecServerBusy(_)));
+    assert!(busy.is_retriable());
+
+    let token = HecResponse::parse(r#"{"text":"Invalid token","code":4}"#).into_error(403);
+    assert!(matches!(token, SplunkError::HecInvalidToken(_)));
+    assert!(!token.is_retriable());
+
+    // a proxy in the way won't send JSON
+    let proxy = HecResponse::parse("<html>Bad Gateway</html>").into_error(502);
+    assert!(matches!(
+        &proxy,
+        SplunkError::HecRequestFailed { status: 502, response } if response.text.contains("Bad Gateway")
+    ));
+    assert!(proxy.is_retriable());
+}
+
+#[test]
+async fn test_hec_health_states() -> Result<(), SplunkError> {
+    use crate::hec::{HecHealth, HecHealthResult};
+
+    let healthy: HecHealthResult = serde_json::from_str(r#"{"text":"HEC is healthy","code":17}"#)?;
+    assert_eq!(healthy.health(), HecHealth::Healthy);
+    assert!(healthy.health().is_healthy());
+
+    let health = |body: &str| -> Result<HecHealth, SplunkError> {
+        Ok(serde_json::from_str::<HecHealthResult>(body)?.health())
+    };
+    assert_eq!(
+        health(r#"{"text":"HEC is unhealthy, queues are full","code":18}"#)?,
+        HecHealth::QueuesFull
+    );
+    assert_eq!(
+        health(r#"{"text":"HEC is unhealthy, ack service unavailable","code":19}"#)?,
+        HecHealth::AckUnavailable
+    );
+    assert_eq!(
+        health(r#"{"text":"Invalid token","code":4}"#)?,
+        HecHealth::InvalidToken
+    );
+    assert_eq!(
+        health(r#"{"text":"Token disabled","code":1}"#)?,
+        HecHealth::Disabled
+    );
+    assert!(matches!(
+        health(r#"{"text":"Something new","code":99}"#)?,
+        HecHealth::Unknown(_)
+    ));
+    Ok(())
+}
+
+#[test]
+async fn test_hec_sender_readiness_check() -> Result<(), SplunkError> {
+    use crate::hec::{HecClient, HecSender, HecSenderConfig};
+    use std::time::Duration;
+
+    // the health check can't succeed, so nothing's flushed until shutdown
+    let mut client = HecClient::new("token", "127.0.0.1");
+    client.serverconfig = client.serverconfig.with_port(1);
+    let sender = HecSender::spawn(
+        client,
+        HecSenderConfig::default()
+            .with_batch_size(1)
+            .with_readiness_check(Duration::from_millis(10)),
+    );
+    sender.send("waiting").await?;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(sender.stats().sent, 0);
+
+    let stats = sender.shutdown().await?;
+    assert_eq!(stats.failed, 1);
+    Ok(())
+}
+
+#[test]
+async fn test_hec_balancer_strategies() -> Result<(), SplunkError> {
+    use crate::hec::{BalanceStrategy, BalancerConfig, HecBalancer, HecClient};
+
+    assert!(matches!(
+        HecBalancer::new(Vec::new(), BalancerConfig::default()),
+        Err(SplunkError::NoHecTargets)
+    ));
+
+    let clients = || {
+        (1..=3)
+            .map(|port| {
+                let mut client = HecClient::new("token", "127.0.0.1");
+                client.serverconfig = client.serverconfig.clone().with_port(port);
+                client
+            })
+            .collect::<Vec<HecClient>>()
+    };
+
+    let balancer = HecBalancer::new(clients(), BalancerConfig::default())?;
+    assert_eq!(balancer.candidates(), vec![0, 1, 2]);
+    assert_eq!(balancer.candidates(), vec![1, 2, 0]);
+
+    let sticky = HecBalancer::new(
+        clients(),
+        BalancerConfig::default().with_strategy(BalanceStrategy::StickyByChannel),
+    )?
+    .with_channel("0aecfc8b-4a5d-4b9e-b3c6-1b2a7c2f9d11");
+    let first = sticky.candidates();
+    assert_eq!(sticky.candidates(), first);
+
+    // nothing to stick to without a channel, so it takes turns
+    let unstuck = HecBalancer::new(
+        clients(),
+        BalancerConfig::default().with_strategy(BalanceStrategy::StickyByChannel),
+    )?;
+    assert_eq!(unstuck.candidates(), vec![0, 1, 2]);
+    assert_eq!(unstuck.candidates(), vec![1, 2, 0]);
+    Ok(())
+}
+
+#[test]
+async fn test_hec_balancer_ejects_failed_targets() -> Result<(), SplunkError> {
+    use crate::hec::{BalancerConfig, HecBalancer, HecClient};
+
+    // nothing's listening on these ports, so every target fails
+    let clients = (1..=2)
+        .map(|port| {
+            let mut client = HecClient::new("token", "127.0.0.1");
+            client.serverconfig = client.serverconfig.clone().with_port(port);
+            client
+        })
+        .collect();
+    let balancer = HecBalancer::new(clients, BalancerConfig::default())?;
+
+    assert!(balancer.send_event("hello").await.is_err());
+    let targets = balancer.targets();
+    assert!(targets
+        .iter()
+        .all(|target| target.ejected && target.failures == 1 && target.outstanding == 0));
+    // everything's ejected, so they're tried in the order they'll come back
+    assert_eq!(balancer.candidates(), vec![0, 1]);
+
+    assert!(balancer.send_event("hello again").await.is_err());
+    assert!(balancer.targets().iter().all(|target| target.failures == 2));
+    Ok(())
+}
+
+#[test]
+async fn test_hec_balancer_only_ejects_failed_targets() -> Result<(), SplunkError> {
+    use crate::hec::{BalancerConfig, HecBalancer, HecClient};
+    use std::time::Duration;
+
+    let client = |port: u16| {
+        let mut client = HecClient::new("token", "127.0.0.1");
+        client.serverconfig = client.serverconfig.clone().use_tls(false).with_port(port);
+        client
+    };
+    let nothing_listening = std::net::TcpListener::bind("127.0.0.1:0")?
+        .local_addr()?
+        .port();
+
+    // the event's the problem, so it's not the target's fault and there's no point trying another
+    let (port, _server) = mock_server(vec![(400, r#"{"text":"Incorrect index","code":7}"#)])?;
+    let balancer = HecBalancer::new(
+        vec![client(port), client(nothing_listening)],
+        BalancerConfig::default(),
+    )?;
+    let err = balancer
+        .send_event("hello")
+        .await
+        .expect_err("the index is wrong");
+    assert!(matches!(err, SplunkError::HecIncorrectIndex(_)), "{err:?}");
+    assert!(balancer.targets().iter().all(|target| target.failures == 0));
+
+    // a busy target's ejected and the next one gets the events
+    let (busy_port, busy_server) = mock_server(vec![
+        (503, r#"{"text":"Server is busy","code":9}"#),
+        (200, r#"{"text":"HEC is healthy","code":17}"#),
+        (200, r#"{"text":"Success","code":0}"#),
+    ])?;
+    let (port, _server) = mock_server(vec![(200, r#"{"text":"Success","code":0}"#)])?;
+    let balancer = HecBalancer::new(
+        vec![client(busy_port), client(port)],
+        BalancerConfig::default().with_initial_backoff(Duration::from_millis(500)),
+    )?;
+    assert!(balancer.send_event("hello").await?.is_success());
+    let targets = balancer.targets();
+    assert!(targets[0].ejected && targets[0].failures == 1);
+    assert_eq!(targets[1].failures, 0);
+
+    // the second target's gone now, and the first's still backing off so it's left alone
+    balancer
+        .send_event("hello again")
+        .await
+        .expect_err("the second target's gone");
+    assert!(balancer.targets().iter().all(|target| target.failures == 1));
+
+    // once its backoff's up, the ejected one's health checked before it's used again
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert!(balancer.send_event("third time").await?.is_success());
+    let requests = busy_server
+        .join()
+        .map_err(|_| SplunkError::Generic("mock server failed".to_string()))?;
+    assert_eq!(requests.len(), 3);
+    assert!(
+        requests[1].0.starts_with("GET /services/collector/health"),
+        "{}",
+        requests[1].0
+    );
+    assert!(requests[2].1.contains("third time"));
+    Ok(())
+}
+
+#[test]
+async fn test_hec_url_building() -> Result<(), SplunkError> {
+    use crate::ServerConfig;
+
+    let config = ServerConfig::new("example.com".to_string());
+    assert_eq!(
+        config.clone().with_port(443).get_url("/services")?.as_str(),
+        "https://example.com/services"
+    );
+    // the port is left out based on the scheme, not whether we check certificates
+    assert_eq!(
+        config
+            .clone()
+            .with_port(443)
+            .with_verify_tls(false)
+            .get_url("/services")?
+            .as_str(),
+        "https://example.com/services"
+    );
+    assert_eq!(
+        config
+            .clone()
+            .use_tls(false)
+            .with_port(80)
+            .get_url("/services")?
+            .as_str(),
+        "http://example.com/services"
+    );
+    assert_eq!(
+        config
+            .clone()
+            .use_tls(false)
+            .with_port(443)
+            .get_url("/services")?
+            .as_str(),
+        "http://example.com:443/services"
+    );
+    assert_eq!(
+        config
+            .with_port(8088)
+            .with_base_path("splunk/hec/")
+            .get_url("/services/collector")?
+            .as_str(),
+        "https://example.com:8088/splunk/hec/services/collector"
+    );
+    Ok(())
+}
+
+#[test]
+async fn test_hec_plain_http_with_base_path() -> Result<(), SplunkError> {
+    use crate::hec::HecClient;
+
+    let (port, server) = mock_server(vec![(200, r#"{"text":"Success","code":0}"#)])?;
+    let mut client = HecClient::new("token", "127.0.0.1");
+    client.serverconfig = client
+        .serverconfig
+        .use_tls(false)
+        .with_port(port)
+        .with_base_path("/splunk/hec");
+
+    let response = client.send_event("hello").await?;
+    assert!(response.is_success());
+
+    let requests = server
+        .join()
+        .map_err(|_| SplunkError::Generic("mock server failed".to_string()))?;
+    assert_eq!(
+        requests[0].0,
+        "POST /splunk/hec/services/collector HTTP/1.1"
+    );
+    assert_eq!(requests[0].1, r#"{"event":"hello"}"#);
+    Ok(())
+}
+
+#[test]
+async fn test_hec_through_proxy() -> Result<(), SplunkError> {
+    use crate::hec::HecClient;
+    use crate::http::HttpConfig;
+
+    let (port, proxy) = mock_server(vec![(200, r#"{"text":"Success","code":0}"#)])?;
+    let mut client = HecClient::new("token", "splunk.invalid");
+    client.serverconfig = client.serverconfig.use_tls(false).with_http_config(
+        HttpConfig::default()
+            .with_proxy(&format!("http://127.0.0.1:{port}"))
+            .with_pool_max_idle_per_host(1),
+    );
+    client.send_event("via the proxy").await?;
+
+    let requests = proxy
+        .join()
+        .map_err(|_| SplunkError::Generic("mock server failed".to_string()))?;
+    assert_eq!(
+        requests[0].0,
+        "POST http://splunk.invalid:8088/services/collector HTTP/1.1"
+    );
+    Ok(())
+}
+
+#[test]
+async fn test_hec_request_timeout() -> Result<(), SplunkError> {
+    use crate::hec::HecClient;
+    use crate::http::Timeouts;
+    use std::time::Duration;
+
+    let port = crate::tests::silent_server()?;
+    let mut client = HecClient::new("token", "127.0.0.1")
+        .with_timeouts(Timeouts::default().with_total(Duration::from_millis(200)));
+    client.serverconfig = client.serverconfig.use_tls(false).with_port(port);
+    let err = client
+        .send_event("nobody's listening")
+        .await
+        .expect_err("the server never answers");
+    assert!(matches!(err, SplunkError::Timeout(_)), "{err:?}");
+    Ok(())
+}
@@ -1,6 +1,116 @@
 /// Test things.
 ///
+mod access;
 mod client;
+mod context;
 mod hec;
+mod profile;
+mod retry;
+mod spool;
+mod tls;
+mod tokens;
 
 mod search;
+
+use std::collections::HashMap;
+
+use crate::errors::SplunkError;
+
+/// The request line, body and headers (with lowercase names) of each request a mock server got
+pub(crate) type MockRequests =
+    std::thread::JoinHandle<Vec<(String, String, HashMap<String, String>)>>;
+
+/// A tiny HEC or REST API stand-in on a random local port, which answers each request in turn with the given status
+/// and body, then stops listening.
+///
+/// The thread returns the request line, body and headers of each request it got.
+pub(crate) fn mock_server(
+    responses: Vec<(u16, &'static str)>,
+) -> Result<(u16, MockRequests), SplunkError> {
+    mock_server_with_headers(
+        responses
+            .into_iter()
+            .map(|(status, body)| (status, Vec::new(), body))
+            .collect(),
+    )
+}
+
+/// A status, extra headers and body for [mock_server_with_headers] to answer with
+pub(crate) type MockResponse = (u16, Vec<(&'static str, String)>, &'static str);
+
+/// Like [mock_server], with extra headers on each response
+pub(crate) fn mock_server_with_headers(
+    responses: Vec<MockResponse>,
+) -> Result<(u16, MockRequests), SplunkError> {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let port = listener.local_addr()?.port();
+    let handle = std::thread::spawn(move || {
+        let mut requests = Vec::new();
+        for (status, response_headers, body) in responses {
+            let Ok((stream, _)) = listener.accept() else {
+                break;
+            };
+            let mut reader = BufReader::new(stream);
+            let mut request_line = String::new();
+            let mut content_length: usize = 0;
+            let mut headers = HashMap::new();
+            let mut line = String::new();
+            if reader.read_line(&mut request_line).is_err() {
+                break;
+            }
+            while reader.read_line(&mut line).is_ok() && line != "\r\n" {
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap_or_default();
+                    }
+                    headers.insert(name.to_ascii_lowercase(), value.trim().to_string());
+                }
+                line.clear();
+            }
+            let mut request_body = vec![0; content_length];
+            if reader.read_exact(&mut request_body).is_err() {
+                break;
+            }
+            requests.push((
+                request_line.trim().to_string(),
+                String::from_utf8_lossy(&request_body).to_string(),
+                headers,
+            ));
+            let response_headers: String = response_headers
+                .iter()
+                .map(|(name, value)| format!("{name}: {value}\r\n"))
+                .collect();
+            let response = format!(
+                "HTTP/1.1 {status} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{response_headers}Connection: close\r\n\r\n{body}",
+                body.len()
+            );
+            if reader.get_mut().write_all(response.as_bytes()).is_err() {
+                break;
+            }
+        }
+        requests
+    });
+    Ok((port, handle))
+}
+
+/// A server on a random local port which accepts connections and never answers, holding them open for a few seconds
+pub(crate) fn silent_server() -> Result<u16, SplunkError> {
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let port = listener.local_addr()?.port();
+    std::thread::spawn(move || {
+        let mut connections = Vec::new();
+        while let Ok((stream, _)) = listener.accept() {
+            connections.push(stream);
+            if connections.len() >= 4 {
+                break;
+            }
+        }
+        std::thread::sleep(std::time::Duration::from_secs(5));
+    });
+    Ok(port)
+}
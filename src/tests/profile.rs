@@ -0,0 +1,147 @@
+use std::fs;
+
+use crate::client::AuthenticationMethod;
+use crate::errors::SplunkError;
+use crate::profile::SplunkRc;
+use crate::secret::Secret;
+use crate::ServerConfigType;
+
+const SPLUNKRC: &str = r#"
+default_profile = "dev"
+
+[profiles.dev]
+hostname = "localhost"
+scheme = "http"
+username = "admin"
+password = "changeme"
+hec_token = "dev-hec-token"
+
+[profiles.prod]
+hostname = "splunk.example.com"
+port = 443
+hec_port = 8443
+verify_tls = false
+base_path = "/splunk/"
+app = "search"
+owner = "nobody"
+token = "prod-api-token"
+hec_token = "prod-hec-token"
+"#;
+
+/// No environment variables set
+fn no_env(_: &str) -> Option<String> {
+    None
+}
+
+#[test]
+async fn test_profile_selection() -> Result<(), SplunkError> {
+    let splunkrc: SplunkRc = SPLUNKRC.parse()?;
+    assert_eq!(splunkrc.profiles.len(), 2);
+    assert_eq!(
+        splunkrc.profile(Some("prod"))?.hostname.as_deref(),
+        Some("splunk.example.com")
+    );
+    assert!(splunkrc.profile(Some("staging")).is_err());
+
+    // unknown keys are typos, not something to ignore
+    assert!("[profiles.dev]\nhostnme = \"localhost\"\n"
+        .parse::<SplunkRc>()
+        .is_err());
+    assert!("[profiles.dev]\nscheme = \"ftp\"\n"
+        .parse::<SplunkRc>()
+        .is_err());
+
+    // secrets stay out of debug output
+    let debug = format!("{:?}", splunkrc);
+    assert!(!debug.contains("changeme"));
+    assert!(!debug.contains("prod-api-token"));
+    Ok(())
+}
+
+#[test]
+async fn test_profile_server_config() -> Result<(), SplunkError> {
+    let splunkrc: SplunkRc = SPLUNKRC.parse()?;
+
+    let dev = splunkrc.profile(Some("dev"))?;
+    let api = dev.server_config_with_env(ServerConfigType::Api, no_env)?;
+    assert_eq!(
+        api.get_url("/services")?.as_str(),
+        "http://localhost:8089/services"
+    );
+    assert!(matches!(
+        &api.auth_method,
+        AuthenticationMethod::Basic { username, password } if username == "admin" && password.expose() == "changeme"
+    ));
+    let hec = dev.server_config_with_env(ServerConfigType::Hec, no_env)?;
+    assert_eq!(hec.port, 8088);
+    assert_eq!(hec.token().map(Secret::expose), Some("dev-hec-token"));
+
+    let prod = splunkrc.profile(Some("prod"))?;
+    let api = prod.server_config_with_env(ServerConfigType::Api, no_env)?;
+    assert_eq!(
+        api.get_url(&api.namespaced_endpoint("/services/saved/searches"))?
+            .as_str(),
+        "https://splunk.example.com/splunk/servicesNS/nobody/search/saved/searches"
+    );
+    assert!(!api.verify_tls);
+    assert_eq!(api.token().map(Secret::expose), Some("prod-api-token"));
+    let hec = prod.server_config_with_env(ServerConfigType::Hec, no_env)?;
+    assert_eq!(hec.port, 8443);
+    assert_eq!(hec.token().map(Secret::expose), Some("prod-hec-token"));
+    assert!(!hec.tls_config().ca_only());
+
+    // ca_only on its own still drops the built-in roots
+    let splunkrc: SplunkRc =
+        "[profiles.locked]\nhostname = \"localhost\"\ntoken = \"api-token\"\nca_only = true\n"
+            .parse()?;
+    let api = splunkrc
+        .profile(Some("locked"))?
+        .server_config_with_env(ServerConfigType::Api, no_env)?;
+    assert!(api.tls_config().ca_only());
+    Ok(())
+}
+
+#[test]
+async fn test_profile_env_overrides_and_token_file() -> Result<(), SplunkError> {
+    let dir = std::env::temp_dir().join(format!("splunk-rs-profile-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir)?;
+    let token_file = dir.join("hec.token");
+    fs::write(&token_file, "file-hec-token\n")?;
+    let splunkrc: SplunkRc = format!(
+        "[profiles.default]\nhostname = \"localhost\"\nhec_token_file = {:?}\n",
+        token_file
+    )
+    .parse()?;
+    let profile = splunkrc.profile(Some("default"))?;
+
+    let hec = profile.server_config_with_env(ServerConfigType::Hec, no_env)?;
+    assert_eq!(hec.token().map(Secret::expose), Some("file-hec-token"));
+
+    let hec = profile.server_config_with_env(ServerConfigType::Hec, |name| match name {
+        "SPLUNK_HEC_HOSTNAME" => Some("hec.example.com".to_string()),
+        "SPLUNK_HEC_PORT" => Some("9088".to_string()),
+        "SPLUNK_HEC_TOKEN" => Some("env-hec-token".to_string()),
+        _ => None,
+    })?;
+    assert_eq!(hec.hostname, "hec.example.com");
+    assert_eq!(hec.port, 9088);
+    assert_eq!(hec.token().map(Secret::expose), Some("env-hec-token"));
+
+    // no API credentials in the profile, but the old unprefixed variables still work
+    assert!(matches!(
+        profile.server_config_with_env(ServerConfigType::Api, no_env),
+        Err(SplunkError::NoAuthMethodSelected)
+    ));
+    let api = profile.server_config_with_env(ServerConfigType::Api, |name| match name {
+        "SPLUNK_USERNAME" => Some("admin".to_string()),
+        "SPLUNK_API_PASSWORD" => Some("hunter2".to_string()),
+        _ => None,
+    })?;
+    assert!(matches!(
+        &api.auth_method,
+        AuthenticationMethod::Basic { username, .. } if username == "admin"
+    ));
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
@@ -0,0 +1,226 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+use reqwest::{Client, StatusCode};
+
+use crate::errors::SplunkError;
+use crate::retry::RetryPolicy;
+use crate::tests::{mock_server, mock_server_with_headers};
+
+#[test]
+async fn test_retry_policy_backoff_grows_and_caps() {
+    let policy = RetryPolicy::default()
+        .with_initial_backoff(Duration::from_millis(100))
+        .with_max_backoff(Duration::from_secs(1))
+        .with_jitter(0.0);
+
+    assert_eq!(policy.backoff(1), Duration::from_millis(100));
+    assert_eq!(policy.backoff(2), Duration::from_millis(200));
+    assert_eq!(policy.backoff(3), Duration::from_millis(400));
+    assert_eq!(policy.backoff(10), Duration::from_secs(1));
+    assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(1));
+}
+
+#[test]
+async fn test_retry_policy_jitter_stays_in_bounds() {
+    let policy = RetryPolicy::default()
+        .with_initial_backoff(Duration::from_millis(100))
+        .with_jitter(0.5);
+
+    for _ in 0..100 {
+        let delay = policy.backoff(1);
+        assert!(delay >= Duration::from_millis(100));
+        assert!(delay <= Duration::from_millis(150));
+    }
+}
+
+#[test]
+async fn test_retry_policy_status_codes() {
+    let policy = RetryPolicy::default();
+    assert!(policy.is_retryable_status(503));
+    assert!(!policy.is_retryable_status(400));
+    assert_eq!(RetryPolicy::none().max_attempts, 1);
+}
+
+/// A policy that retries quickly, so the tests don't hang around
+fn quick_policy() -> RetryPolicy {
+    RetryPolicy::default()
+        .with_initial_backoff(Duration::from_millis(10))
+        .with_max_backoff(Duration::from_millis(50))
+        .with_jitter(0.0)
+}
+
+#[tokio::test]
+async fn test_retry_policy_retries_busy_responses() -> Result<(), SplunkError> {
+    let (port, server) = mock_server(vec![(503, "busy"), (429, "slow down"), (200, "{}")])?;
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:{port}/services/collector/health");
+
+    let response = quick_policy().execute(|| client.get(&url).send()).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let requests = server
+        .join()
+        .map_err(|_| SplunkError::Generic("mock server failed".to_string()))?;
+    assert_eq!(requests.len(), 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_retry_policy_gives_up_after_max_attempts() -> Result<(), SplunkError> {
+    // the server stops listening after three requests, so a fourth would be a connection error
+    let (port, server) = mock_server(vec![(503, "busy"), (503, "busy"), (503, "busy")])?;
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:{port}/");
+
+    let response = quick_policy()
+        .with_max_attempts(3)
+        .execute(|| client.get(&url).send())
+        .await?;
+    // the last response comes back as it is, for the caller to check
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let requests = server
+        .join()
+        .map_err(|_| SplunkError::Generic("mock server failed".to_string()))?;
+    assert_eq!(requests.len(), 3);
+
+    // and things that aren't worth retrying aren't
+    let (port, _server) = mock_server(vec![(400, "bad request")])?;
+    let url = format!("http://127.0.0.1:{port}/");
+    let response = quick_policy().execute(|| client.get(&url).send()).await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_retry_policy_honours_retry_after() -> Result<(), SplunkError> {
+    // the backoff's far longer than the test's allowed to take, so only Retry-After gets it done in time
+    let policy = RetryPolicy::default()
+        .with_initial_backoff(Duration::from_secs(60))
+        .with_max_backoff(Duration::from_secs(60))
+        .with_jitter(0.0);
+    let client = Client::new();
+
+    let (port, _server) = mock_server_with_headers(vec![
+        (503, vec![("Retry-After", "1".to_string())], "busy"),
+        (200, Vec::new(), "{}"),
+    ])?;
+    let url = format!("http://127.0.0.1:{port}/");
+    let started = Instant::now();
+    let response = tokio::time::timeout(
+        Duration::from_secs(10),
+        policy.execute(|| client.get(&url).send()),
+    )
+    .await
+    .map_err(|_| SplunkError::Generic("Retry-After wasn't honoured".to_string()))??;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(started.elapsed() >= Duration::from_secs(1));
+
+    // the HTTP date form, which only has whole seconds
+    let retry_at = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(2));
+    let (port, _server) = mock_server_with_headers(vec![
+        (503, vec![("Retry-After", retry_at)], "busy"),
+        (200, Vec::new(), "{}"),
+    ])?;
+    let url = format!("http://127.0.0.1:{port}/");
+    let started = Instant::now();
+    let response = tokio::time::timeout(
+        Duration::from_secs(10),
+        policy.execute(|| client.get(&url).send()),
+    )
+    .await
+    .map_err(|_| SplunkError::Generic("Retry-After wasn't honoured".to_string()))??;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(started.elapsed() >= Duration::from_millis(900));
+
+    // a date that's already gone means straight away
+    let (port, _server) = mock_server_with_headers(vec![
+        (
+            503,
+            vec![("Retry-After", "Wed, 21 Oct 2015 07:28:00 GMT".to_string())],
+            "busy",
+        ),
+        (200, Vec::new(), "{}"),
+    ])?;
+    let url = format!("http://127.0.0.1:{port}/");
+    let response = tokio::time::timeout(
+        Duration::from_secs(10),
+        policy.execute(|| client.get(&url).send()),
+    )
+    .await
+    .map_err(|_| SplunkError::Generic("Retry-After wasn't honoured".to_string()))??;
+    assert_eq!(response.status(), StatusCode::OK);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_retry_policy_retries_connection_errors() -> Result<(), SplunkError> {
+    // nothing's listening once the listener's dropped
+    let port = std::net::TcpListener::bind("127.0.0.1:0")?
+        .local_addr()?
+        .port();
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:{port}/");
+
+    let attempts = AtomicU32::new(0);
+    let err = quick_policy()
+        .with_max_attempts(3)
+        .execute(|| {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            client.get(&url).send()
+        })
+        .await
+        .expect_err("nothing's listening");
+    assert!(err.is_retriable(), "{err:?}");
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+    let attempts = AtomicU32::new(0);
+    let mut policy = quick_policy().with_max_attempts(3);
+    policy.retry_connection_errors = false;
+    policy
+        .execute(|| {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            client.get(&url).send()
+        })
+        .await
+        .expect_err("nothing's listening");
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_retry_policy_non_idempotent_only_retries_connect_errors() -> Result<(), SplunkError> {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")?
+        .local_addr()?
+        .port();
+    let client = Client::new();
+    let url = format!("http://127.0.0.1:{port}/");
+
+    // it never got to the server, so it's safe to send again
+    let attempts = AtomicU32::new(0);
+    quick_policy()
+        .with_max_attempts(3)
+        .execute_non_idempotent(|| {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            client.post(&url).send()
+        })
+        .await
+        .expect_err("nothing's listening");
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+    // but the server might have acted on one it answered
+    let (port, server) = mock_server(vec![(503, "busy"), (200, "{}")])?;
+    let url = format!("http://127.0.0.1:{port}/");
+    let response = quick_policy()
+        .execute_non_idempotent(|| client.post(&url).send())
+        .await?;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    // let the server finish
+    client.get(&url).send().await?;
+    let requests = server
+        .join()
+        .map_err(|_| SplunkError::Generic("mock server failed".to_string()))?;
+    assert_eq!(requests.len(), 2);
+    assert!(requests[0].0.starts_with("POST"));
+    Ok(())
+}
@@ -0,0 +1,171 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde_json::json;
+
+use crate::errors::SplunkError;
+use crate::hec::{HecClient, HecEvent, HecSpool, SpoolConfig};
+use crate::tests::mock_server;
+
+/// A fresh directory for each test
+fn spool_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("splunk-rs-spool-{}", uuid::Uuid::new_v4()))
+}
+
+#[test]
+async fn test_spool_replay_and_commit() -> Result<(), SplunkError> {
+    let dir = spool_dir();
+    {
+        let mut spool = HecSpool::open(&dir, SpoolConfig::default())?;
+        let events: Vec<HecEvent> = (0..5)
+            .map(|i| HecEvent::new(json!({ "event": i })).with_host("web01"))
+            .collect();
+        assert_eq!(spool.append(&events)?, 0);
+        spool.commit(2)?;
+        assert_eq!(spool.len(), 3);
+    }
+
+    let spool = HecSpool::open(&dir, SpoolConfig::default())?;
+    let pending = spool
+        .pending()
+        .collect::<Result<Vec<HecEvent>, SplunkError>>()?;
+    assert_eq!(pending.len(), 3);
+    assert_eq!(pending[0].event, json!({ "event": 2 }));
+    assert_eq!(pending[0].host.as_deref(), Some("web01"));
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+async fn test_spool_segments_and_retention() -> Result<(), SplunkError> {
+    let dir = spool_dir();
+    let config = SpoolConfig::default()
+        .with_max_segment_bytes(100)
+        .with_max_total_bytes(250);
+    let mut spool = HecSpool::open(&dir, config.clone())?;
+
+    let event = HecEvent::new("x".repeat(80));
+    // every event is over 80 bytes, so each one gets its own segment
+    let mut dropped: u64 = 0;
+    for _ in 0..5 {
+        dropped += spool.append(std::slice::from_ref(&event))?;
+    }
+    assert!(spool.disk_usage() <= 250);
+    assert_eq!(dropped, 3);
+    assert_eq!(spool.len(), 2);
+
+    // committing everything removes the segment files
+    spool.commit(2)?;
+    assert!(spool.is_empty());
+    assert_eq!(spool.disk_usage(), 0);
+    drop(spool);
+    assert!(HecSpool::open(&dir, config)?.is_empty());
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+async fn test_spool_corrupt_tail() -> Result<(), SplunkError> {
+    let dir = spool_dir();
+    {
+        let mut spool = HecSpool::open(&dir, SpoolConfig::default())?;
+        spool.append(&[HecEvent::new("one"), HecEvent::new("two")])?;
+    }
+    // simulate a crash part way through writing a record
+    let segment = dir.join(format!("{:020}.spool", 0));
+    let mut data = fs::read(&segment)?;
+    let length = data.len();
+    data.truncate(length - 3);
+    fs::write(&segment, data)?;
+
+    let mut spool = HecSpool::open(&dir, SpoolConfig::default())?;
+    assert_eq!(spool.len(), 1);
+    // new records go after the last good one
+    spool.append(&[HecEvent::new("three")])?;
+    drop(spool);
+
+    let events: Vec<serde_json::Value> = HecSpool::open(&dir, SpoolConfig::default())?
+        .pending()
+        .map(|event| event.map(|event| event.event))
+        .collect::<Result<_, SplunkError>>()?;
+    assert_eq!(events, vec![json!("one"), json!("three")]);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+async fn test_spool_keeps_events_when_flush_fails() -> Result<(), SplunkError> {
+    let dir = spool_dir();
+    {
+        // nothing's listening on this port, so the flush fails
+        let mut client = HecClient::new("token", "127.0.0.1");
+        client.serverconfig = client.serverconfig.with_port(1);
+        client.enqueue("before the spool").await?;
+        assert_eq!(
+            client
+                .attach_spool(HecSpool::open(&dir, SpoolConfig::default())?)
+                .await?,
+            0
+        );
+        client.enqueue(json!({ "hello": "world" })).await?;
+
+        assert!(client.flush(None).await.is_err());
+        assert_eq!(client.queue_size().await, 2);
+        assert_eq!(client.spool().await.map(|spool| spool.len()), Some(2));
+    }
+
+    // the next run picks up where we left off
+    let mut client = HecClient::new("token", "127.0.0.1");
+    let replayed = client
+        .attach_spool(HecSpool::open(&dir, SpoolConfig::default())?)
+        .await?;
+    assert_eq!(replayed, 2);
+    assert_eq!(client.queue_size().await, 2);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+async fn test_spool_replays_a_bit_at_a_time() -> Result<(), SplunkError> {
+    let dir = spool_dir();
+    let config = SpoolConfig::default().with_max_queued_events(2);
+    {
+        let mut spool = HecSpool::open(&dir, config.clone())?;
+        let events: Vec<HecEvent> = (0..5).map(|i| HecEvent::new(json!(i))).collect();
+        spool.append(&events)?;
+    }
+
+    let success = r#"{"text":"Success","code":0}"#;
+    let (port, server) = mock_server(vec![(200, success), (200, success), (200, success)])?;
+    let mut client = HecClient::new("token", "127.0.0.1");
+    client.serverconfig = client.serverconfig.use_tls(false).with_port(port);
+    assert_eq!(client.attach_spool(HecSpool::open(&dir, config)?).await?, 5);
+    // the queue's full, so this waits on disk behind the rest
+    client.enqueue(json!(5)).await?;
+    assert_eq!(client.queue_size().await, 6);
+
+    let report = client.flush(Some(2)).await?;
+    assert_eq!(report.sent, 6);
+    assert_eq!(client.queue_size().await, 0);
+    assert_eq!(client.spool().await.map(|spool| spool.len()), Some(0));
+
+    let requests = server
+        .join()
+        .map_err(|_| SplunkError::Generic("mock server failed".to_string()))?;
+    let bodies: Vec<&str> = requests.iter().map(|request| request.1.as_str()).collect();
+    assert_eq!(
+        bodies,
+        vec![
+            "{\"event\":0}\n{\"event\":1}",
+            "{\"event\":2}\n{\"event\":3}",
+            "{\"event\":4}\n{\"event\":5}",
+        ]
+    );
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use crate::errors::SplunkError;
+use crate::tests::mock_server;
+use crate::tls::TlsConfig;
+use crate::ServerConfig;
+
+const CA_PEM: &[u8] = include_bytes!("certs/ca.pem");
+const CLIENT_PEM: &[u8] = include_bytes!("certs/client.pem");
+const CLIENT_KEY: &[u8] = include_bytes!("certs/client.key");
+const SERVER_PEM: &[u8] = include_bytes!("certs/server.pem");
+const SERVER_KEY: &[u8] = include_bytes!("certs/server.key");
+/// `openssl x509 -in server.pem -noout -fingerprint -sha256`
+const SERVER_FINGERPRINT: &str =
+    "72:48:68:3A:09:15:60:C9:2A:0E:16:10:35:50:53:F0:A1:13:48:D5:0C:45:FD:A5:6F:77:CD:23:2E:CD:38:46";
+/// The SHA-256 of the server's public key, as `curl --pinnedpubkey` takes it
+const SERVER_SPKI_PIN: &str = "sha256//pH5a4OscR+2PiWYCEDQjHRghDouCmz+e3koGE0DtwuE=";
+
+/// A TLS server with `server.pem` on a random local port, which answers each of `connections` with an empty 200
+fn tls_server(connections: usize) -> Result<(u16, std::thread::JoinHandle<()>), SplunkError> {
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+    use std::io::{BufRead, BufReader, Write};
+    use std::sync::Arc;
+
+    let chain = CertificateDer::pem_slice_iter(SERVER_PEM)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| SplunkError::Generic(err.to_string()))?;
+    let key = PrivateKeyDer::from_pem_slice(SERVER_KEY)
+        .map_err(|err| SplunkError::Generic(err.to_string()))?;
+    let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::aws_lc_rs::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .and_then(|builder| builder.with_no_client_auth().with_single_cert(chain, key))
+    .map_err(|err| SplunkError::Generic(err.to_string()))?;
+    let config = Arc::new(config);
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let port = listener.local_addr()?.port();
+    let handle = std::thread::spawn(move || {
+        for _ in 0..connections {
+            let Ok((stream, _)) = listener.accept() else {
+                break;
+            };
+            let Ok(connection) = rustls::ServerConnection::new(config.clone()) else {
+                break;
+            };
+            // a rejected certificate fails the handshake here, which is fine
+            let mut reader = BufReader::new(rustls::StreamOwned::new(connection, stream));
+            let mut line = String::new();
+            while reader.read_line(&mut line).is_ok_and(|read| read > 0) && line != "\r\n" {
+                line.clear();
+            }
+            let _ = reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n");
+            let _ = reader.get_mut().flush();
+        }
+    });
+    Ok((port, handle))
+}
+
+#[test]
+async fn test_tls_config_loads_certificates() -> Result<(), SplunkError> {
+    let tls = TlsConfig::default()
+        .with_ca_pem(CA_PEM)?
+        .with_ca_only(true)
+        .with_client_identity_pem(CLIENT_PEM, CLIENT_KEY)?;
+
+    // the private key never ends up in logs
+    let debug = format!("{:?}", tls);
+    assert!(debug.contains("<redacted>"));
+    assert!(!debug.contains("PRIVATE KEY"));
+
+    assert!(TlsConfig::default()
+        .with_ca_pem(b"not a certificate")
+        .is_err());
+    assert!(TlsConfig::default()
+        .with_client_identity_pem(CLIENT_PEM, b"not a key")
+        .is_err());
+    assert!(TlsConfig::default()
+        .with_ca_file("/this/file/does/not/exist.pem")
+        .is_err());
+
+    let config = ServerConfig::new("127.0.0.1".to_string()).with_tls_config(tls);
+    config.client_builder()?.build()?;
+    Ok(())
+}
+
+#[test]
+async fn test_tls_server_name_override() -> Result<(), SplunkError> {
+    let config = ServerConfig::new("127.0.0.1".to_string())
+        .with_port(8089)
+        .with_tls_config(TlsConfig::default().with_server_name("splunk.example.com"));
+
+    // we ask for the server name, and the client resolves it to the hostname
+    assert_eq!(
+        config.get_url("/services")?.as_str(),
+        "https://splunk.example.com:8089/services"
+    );
+    assert_eq!(
+        config.tls_config().server_name(),
+        Some("splunk.example.com")
+    );
+    config.client_builder()?.build()?;
+    Ok(())
+}
+
+#[test]
+async fn test_tls_server_name_resolved_per_connection() -> Result<(), SplunkError> {
+    // plain HTTP is enough to see the server name's sent to the hostname
+    let (port, server) = mock_server(vec![(200, "{}")])?;
+    let mut config = ServerConfig::new("splunk.invalid".to_string())
+        .use_tls(false)
+        .with_port(port)
+        .with_tls_config(TlsConfig::default().with_server_name("splunk.example.com"));
+    // a hostname that doesn't resolve doesn't stop the client being built
+    config.http_client()?;
+    // and changing it afterwards isn't stuck with the old one
+    config.hostname = "127.0.0.1".to_string();
+    let response = config
+        .http_client()?
+        .get(config.get_url("/services/server/info")?)
+        .send()
+        .await?;
+    assert!(response.status().is_success());
+
+    let requests = server
+        .join()
+        .map_err(|_| SplunkError::Generic("mock server failed".to_string()))?;
+    assert_eq!(
+        requests[0].2.get("host").map(String::as_str),
+        Some(format!("splunk.example.com:{port}").as_str())
+    );
+    Ok(())
+}
+
+#[test]
+async fn test_tls_pins() -> Result<(), SplunkError> {
+    let tls = TlsConfig::default()
+        .with_spki_pin(SERVER_SPKI_PIN)?
+        .with_certificate_pin(&SERVER_FINGERPRINT.replace(':', "").to_lowercase())?;
+    let debug = format!("{:?}", tls);
+    assert!(debug.contains(SERVER_SPKI_PIN));
+    assert!(debug.contains(SERVER_FINGERPRINT));
+
+    TlsConfig::default()
+        .with_spki_pin("sha256//not base64")
+        .expect_err("an invalid pin should be rejected");
+    TlsConfig::default()
+        .with_spki_pin("c2hvcnQ=")
+        .expect_err("a pin that isn't a SHA-256 should be rejected");
+    TlsConfig::default()
+        .with_certificate_pin("72:48:68")
+        .expect_err("a short fingerprint should be rejected");
+    Ok(())
+}
+
+#[test]
+async fn test_tls_pinned_certificate() -> Result<(), SplunkError> {
+    let (port, server) = tls_server(4)?;
+    let connect = |tls: TlsConfig, verify_tls: bool| {
+        let config = ServerConfig::new("127.0.0.1".to_string())
+            .with_port(port)
+            .with_verify_tls(verify_tls)
+            .with_tls_config(tls);
+        async move {
+            config
+                .http_client()?
+                .get(config.get_url("/services/server/info")?)
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok::<(), SplunkError>(())
+        }
+    };
+    let trusted = TlsConfig::default()
+        .with_ca_pem(SERVER_PEM)?
+        .with_ca_only(true);
+
+    // a backup pin that doesn't match doesn't get in the way
+    connect(
+        trusted
+            .clone()
+            .with_certificate_pin(&"00".repeat(32))?
+            .with_spki_pin(SERVER_SPKI_PIN)?,
+        true,
+    )
+    .await?;
+    connect(
+        trusted.clone().with_certificate_pin(SERVER_FINGERPRINT)?,
+        true,
+    )
+    .await?;
+    // a certificate that's trusted but doesn't match the pin is rejected
+    connect(trusted.with_certificate_pin(&"00".repeat(32))?, true)
+        .await
+        .expect_err("a certificate that doesn't match the pin should be rejected");
+    // with validation off, the pin's all that's checked
+    connect(TlsConfig::default().with_spki_pin(SERVER_SPKI_PIN)?, false).await?;
+
+    server
+        .join()
+        .map_err(|_| SplunkError::Generic("TLS server failed".to_string()))?;
+    Ok(())
+}
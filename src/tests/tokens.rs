@@ -0,0 +1,116 @@
+use crate::client::{NewToken, TokenStatus};
+use crate::errors::SplunkError;
+use crate::secret::Secret;
+use crate::tests::client::mock_client;
+use crate::tests::mock_server;
+use crate::ServerConfig;
+
+const TOKEN_LIST: &str = r#"{"entry":[
+    {"name":"0a1b2c","content":{
+        "claims":{"sub":"svc_ingest","aud":"ingest pipeline","iss":"admin from sh1","iat":1700000000,"exp":1702592000,"nbr":1700000000,"idp":"splunk","roles":["user"]},
+        "status":"enabled","lastUsed":1701000000,"lastUsedIp":"10.0.0.7"}},
+    {"name":"3d4e5f","content":{
+        "claims":{"sub":"svc_ingest","aud":"adhoc","iss":"admin from sh1","iat":1690000000,"exp":0,"nbr":0},
+        "status":"disabled","lastUsed":0,"lastUsedIp":""}}
+]}"#;
+
+#[test]
+async fn test_token_lifecycle() -> Result<(), SplunkError> {
+    let (port, server) = mock_server(vec![
+        (
+            200,
+            r#"{"entry":[{"name":"tokens_auth","content":{"disabled":"0"}}]}"#,
+        ),
+        (200, TOKEN_LIST),
+        (
+            201,
+            r#"{"entry":[{"name":"tokens","content":{"id":"6a7b8c","token":"eyJraWQ"}}]}"#,
+        ),
+        (200, "{}"),
+        (200, "{}"),
+        (200, "{}"),
+    ])?;
+    let client = mock_client(
+        port,
+        ServerConfig::new("127.0.0.1".to_string()).with_token("admin-token"),
+    )?;
+
+    assert!(client.token_auth_enabled().await?);
+
+    let tokens = client.list_tokens(Some("svc_ingest")).await?;
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(tokens[0].id, "0a1b2c");
+    assert_eq!(tokens[0].owner, "svc_ingest");
+    assert_eq!(tokens[0].audience, "ingest pipeline");
+    assert_eq!(tokens[0].status, TokenStatus::Enabled);
+    assert_eq!(tokens[0].expires_at, Some(1702592000));
+    assert_eq!(tokens[0].last_used_ip.as_deref(), Some("10.0.0.7"));
+    assert!(!tokens[0].is_expired(1701000000));
+    assert!(tokens[0].is_expired(1702592000));
+    // zeroes and empty strings mean it's not set
+    assert_eq!(tokens[1].status, TokenStatus::Disabled);
+    assert_eq!(tokens[1].expires_at, None);
+    assert_eq!(tokens[1].last_used, None);
+    assert_eq!(tokens[1].last_used_ip, None);
+    assert!(!tokens[1].is_expired(i64::MAX));
+
+    let created = client
+        .create_token(
+            NewToken::new("svc_ingest", "ingest pipeline")
+                .with_expires_on("+35d")
+                .with_not_before("+1h"),
+        )
+        .await?;
+    assert_eq!(created.id, "6a7b8c");
+    assert_eq!(created.token.expose(), "eyJraWQ");
+    assert!(!format!("{created:?}").contains("eyJraWQ"));
+
+    client.disable_token("svc_ingest", "0a1b2c").await?;
+    client.enable_token("svc_ingest", "3d4e5f").await?;
+    client.delete_token("svc_ingest", "0a1b2c").await?;
+
+    let requests = server
+        .join()
+        .map_err(|_| SplunkError::Generic("mock server failed".to_string()))?;
+    assert_eq!(
+        requests[0].0,
+        "GET /services/admin/token-auth/tokens_auth?output_mode=json HTTP/1.1"
+    );
+    assert_eq!(
+        requests[1].0,
+        "GET /services/authorization/tokens?count=0&username=svc_ingest&output_mode=json HTTP/1.1"
+    );
+    assert_eq!(
+        requests[2].1,
+        "name=svc_ingest&audience=ingest+pipeline&expires_on=%2B35d&not_before=%2B1h"
+    );
+    assert_eq!(
+        requests[3].0,
+        "POST /services/authorization/tokens/svc_ingest HTTP/1.1"
+    );
+    assert_eq!(requests[3].1, "id=0a1b2c&status=disabled");
+    assert_eq!(requests[4].1, "id=3d4e5f&status=enabled");
+    assert_eq!(
+        requests[5].0,
+        "DELETE /services/authorization/tokens/svc_ingest?id=0a1b2c HTTP/1.1"
+    );
+    assert_eq!(
+        requests[5].2.get("authorization").map(String::as_str),
+        Some("Splunk admin-token")
+    );
+    Ok(())
+}
+
+#[test]
+async fn test_token_auth_disabled() -> Result<(), SplunkError> {
+    let (port, _server) = mock_server(vec![(
+        200,
+        r#"{"entry":[{"name":"tokens_auth","content":{"disabled":true}}]}"#,
+    )])?;
+    let client = mock_client(
+        port,
+        ServerConfig::new("127.0.0.1".to_string()).with_token(Secret::new("admin-token")),
+    )?;
+    assert!(!client.token_auth_enabled().await?);
+    Ok(())
+}
@@ -0,0 +1,440 @@
+//! TLS settings for servers with a private CA, mutual TLS, a certificate that doesn't match the hostname, or a pinned
+//! certificate
+//!
+//! ```no_run
+//! use splunk::tls::TlsConfig;
+//! use splunk::ServerConfig;
+//! # fn example() -> Result<(), splunk::errors::SplunkError> {
+//! let tls = TlsConfig::default()
+//!     .with_ca_file("/etc/pki/splunk-ca.pem")?
+//!     .with_ca_only(true)
+//!     .with_client_identity_files("/etc/pki/hec-client.pem", "/etc/pki/hec-client.key")?
+//!     .with_server_name("splunk.internal.example.com")
+//!     .with_spki_pin("sha256//47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=")?;
+//! let serverconfig = ServerConfig::new("10.0.0.5".to_string()).with_tls_config(tls);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Debug;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+
+use aws_lc_rs::digest::{digest, SHA256};
+use base64::Engine;
+use log::debug;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{Certificate, ClientBuilder, Identity};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{CertificateError, DigitallySignedStruct, RootCertStore, SignatureScheme};
+
+use crate::errors::SplunkError;
+
+#[derive(Clone, Default)]
+/// Certificates to trust, the client certificate to present, the name to expect on the server's certificate and the
+/// pins it has to match
+pub struct TlsConfig {
+    /// PEM-encoded CA certificates
+    ca_pem: Vec<u8>,
+    /// Only trust `ca_pem`, not the built-in roots
+    ca_only: bool,
+    /// PEM-encoded client certificate chain and private key
+    identity_pem: Option<Vec<u8>>,
+    /// Use this name for SNI and certificate validation instead of the hostname
+    server_name: Option<String>,
+    /// The server's certificate has to match one of these
+    pins: Vec<Pin>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+/// A SHA-256 fingerprint the server's own (end-entity) certificate has to match
+enum Pin {
+    /// Of the DER-encoded SubjectPublicKeyInfo, so it survives the certificate being renewed with the same key
+    Spki([u8; 32]),
+    /// Of the whole DER-encoded certificate
+    Certificate([u8; 32]),
+}
+
+impl Debug for Pin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pin::Spki(hash) => write!(
+                f,
+                "sha256//{}",
+                base64::engine::general_purpose::STANDARD.encode(hash)
+            ),
+            Pin::Certificate(hash) => {
+                let hex: Vec<String> = hash.iter().map(|byte| format!("{byte:02X}")).collect();
+                write!(f, "{}", hex.join(":"))
+            }
+        }
+    }
+}
+
+impl Debug for TlsConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsConfig")
+            .field("ca_pem", &format!("{} bytes", self.ca_pem.len()))
+            .field("ca_only", &self.ca_only)
+            .field(
+                "identity_pem",
+                &self.identity_pem.as_ref().map(|_| "<redacted>"),
+            )
+            .field("server_name", &self.server_name)
+            .field("pins", &self.pins)
+            .finish()
+    }
+}
+
+impl TlsConfig {
+    /// Trust the CA certificates in a PEM file, as well as the built-in roots (unless [TlsConfig::with_ca_only])
+    pub fn with_ca_file(self, path: impl AsRef<Path>) -> Result<Self, SplunkError> {
+        let pem = read_file(path.as_ref())?;
+        self.with_ca_pem(&pem)
+    }
+
+    /// Trust the PEM-encoded CA certificates, as well as the built-in roots (unless [TlsConfig::with_ca_only])
+    pub fn with_ca_pem(mut self, pem: &[u8]) -> Result<Self, SplunkError> {
+        if Certificate::from_pem_bundle(pem)?.is_empty() {
+            return Err(SplunkError::Generic(
+                "No certificates found in the CA bundle".to_string(),
+            ));
+        }
+        self.ca_pem.extend_from_slice(pem);
+        self.ca_pem.push(b'\n');
+        Ok(self)
+    }
+
+    /// Only trust the configured CA certificates, ignoring the built-in roots.
+    ///
+    /// Any certificate those CAs have signed for the server's name is accepted - this restricts which CAs are trusted,
+    /// it doesn't pin the server's own certificate or key, use [TlsConfig::with_spki_pin] for that. A self-signed
+    /// server certificate can be added as its own CA.
+    pub fn with_ca_only(mut self, ca_only: bool) -> Self {
+        self.ca_only = ca_only;
+        self
+    }
+
+    /// Whether only the configured CA certificates are trusted
+    pub fn ca_only(&self) -> bool {
+        self.ca_only
+    }
+
+    /// Present a client certificate for mutual TLS, from PEM files holding the certificate (chain) and private key
+    pub fn with_client_identity_files(
+        self,
+        certificate: impl AsRef<Path>,
+        key: impl AsRef<Path>,
+    ) -> Result<Self, SplunkError> {
+        let certificate = read_file(certificate.as_ref())?;
+        let key = read_file(key.as_ref())?;
+        self.with_client_identity_pem(&certificate, &key)
+    }
+
+    /// Present a client certificate for mutual TLS, from the PEM-encoded certificate (chain) and private key
+    pub fn with_client_identity_pem(
+        mut self,
+        certificate: &[u8],
+        key: &[u8],
+    ) -> Result<Self, SplunkError> {
+        let mut identity = Vec::with_capacity(certificate.len() + key.len() + 1);
+        identity.extend_from_slice(key);
+        identity.push(b'\n');
+        identity.extend_from_slice(certificate);
+        // check it parses now, rather than when the first request's sent
+        Identity::from_pem(&identity)?;
+        self.identity_pem = Some(identity);
+        Ok(self)
+    }
+
+    /// Expect this name on the server's certificate (and send it for SNI), while still connecting to the hostname
+    pub fn with_server_name(mut self, server_name: &str) -> Self {
+        self.server_name = Some(server_name.to_string());
+        self
+    }
+
+    /// The name we expect on the server's certificate, if it's not the hostname
+    pub fn server_name(&self) -> Option<&str> {
+        self.server_name.as_deref()
+    }
+
+    /// Only accept a server certificate whose public key has this SHA-256 hash, given in base64 the way `curl
+    /// --pinnedpubkey` and HPKP take it, with or without the `sha256//` prefix.
+    ///
+    /// Call it again to add a backup pin - the certificate has to match one of them. Only the server's own
+    /// certificate is checked, not the CAs above it, and it still has to pass the usual validation unless that's
+    /// turned off with [crate::ServerConfig::with_verify_tls].
+    pub fn with_spki_pin(mut self, pin: &str) -> Result<Self, SplunkError> {
+        let pin = pin.trim();
+        let hash = base64::engine::general_purpose::STANDARD
+            .decode(pin.strip_prefix("sha256//").unwrap_or(pin))
+            .ok()
+            .and_then(|hash| <[u8; 32]>::try_from(hash).ok())
+            .ok_or_else(|| {
+                SplunkError::Generic(format!("{pin} isn't a base64 SHA-256 public key pin"))
+            })?;
+        self.pins.push(Pin::Spki(hash));
+        Ok(self)
+    }
+
+    /// Only accept this server certificate, given as the hex SHA-256 fingerprint of the DER certificate, with or
+    /// without colons (as `openssl x509 -noout -fingerprint -sha256` prints it).
+    ///
+    /// Call it again to add a backup pin, see [TlsConfig::with_spki_pin].
+    pub fn with_certificate_pin(mut self, fingerprint: &str) -> Result<Self, SplunkError> {
+        let hex: String = fingerprint
+            .trim()
+            .chars()
+            .filter(|character| *character != ':')
+            .collect();
+        let hash = (hex.len() == 64 && hex.is_ascii())
+            .then(|| {
+                (0..32)
+                    .map(|index| u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16))
+                    .collect::<Result<Vec<u8>, _>>()
+                    .ok()
+            })
+            .flatten()
+            .and_then(|hash| <[u8; 32]>::try_from(hash).ok())
+            .ok_or_else(|| {
+                SplunkError::Generic(format!(
+                    "{fingerprint} isn't a hex SHA-256 certificate fingerprint"
+                ))
+            })?;
+        self.pins.push(Pin::Certificate(hash));
+        Ok(self)
+    }
+
+    /// Apply the settings to a client that'll connect to `hostname`
+    pub(crate) fn apply(
+        &self,
+        mut builder: ClientBuilder,
+        hostname: &str,
+        verify_tls: bool,
+        http2_only: bool,
+    ) -> Result<ClientBuilder, SplunkError> {
+        if !self.pins.is_empty() {
+            // reqwest can't check pins itself, so hand it a rustls config that does (and everything else it would)
+            debug!("Pinning the server certificate to {:?}", self.pins);
+            builder =
+                builder.tls_backend_preconfigured(self.pinned_config(verify_tls, http2_only)?);
+        } else if !self.ca_pem.is_empty() || self.ca_only {
+            let certificates = Certificate::from_pem_bundle(&self.ca_pem)?;
+            debug!(
+                "Adding {} CA certificates, CA only: {}",
+                certificates.len(),
+                self.ca_only
+            );
+            builder = match self.ca_only {
+                true => builder.tls_certs_only(certificates),
+                false => builder.tls_certs_merge(certificates),
+            };
+        }
+        if let Some(identity) = &self.identity_pem {
+            builder = builder.identity(Identity::from_pem(identity)?);
+        }
+        if let Some(server_name) = &self.server_name {
+            // the URL uses the server name, so point that name at the real host
+            builder = builder.dns_resolver(ServerNameResolver {
+                server_name: server_name.clone(),
+                hostname: hostname.to_string(),
+            });
+        }
+        Ok(builder)
+    }
+
+    /// A rustls config with the CA, client identity and pin settings
+    fn pinned_config(
+        &self,
+        verify_tls: bool,
+        http2_only: bool,
+    ) -> Result<rustls::ClientConfig, SplunkError> {
+        let provider = CryptoProvider::get_default()
+            .cloned()
+            .unwrap_or_else(|| Arc::new(rustls::crypto::aws_lc_rs::default_provider()));
+        let ca_certificates = CertificateDer::pem_slice_iter(&self.ca_pem)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(tls_error)?;
+        let inner: Option<Arc<dyn ServerCertVerifier>> = match (verify_tls, self.ca_only) {
+            (false, _) => None,
+            (true, true) => {
+                let mut roots = RootCertStore::empty();
+                roots.add_parsable_certificates(ca_certificates);
+                Some(
+                    WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                        .build()
+                        .map_err(tls_error)?,
+                )
+            }
+            (true, false) => Some(Arc::new(
+                rustls_platform_verifier::Verifier::new_with_extra_roots(
+                    ca_certificates,
+                    provider.clone(),
+                )
+                .map_err(tls_error)?,
+            )),
+        };
+        let verifier = PinnedVerifier {
+            inner,
+            pins: self.pins.clone(),
+            provider: provider.clone(),
+        };
+        let builder = rustls::ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(tls_error)?
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(verifier));
+        let mut config = match &self.identity_pem {
+            Some(identity) => {
+                let chain = CertificateDer::pem_slice_iter(identity)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(tls_error)?;
+                let key = PrivateKeyDer::from_pem_slice(identity).map_err(tls_error)?;
+                builder
+                    .with_client_auth_cert(chain, key)
+                    .map_err(tls_error)?
+            }
+            None => builder.with_no_client_auth(),
+        };
+        config.alpn_protocols = match http2_only {
+            true => vec![b"h2".to_vec()],
+            false => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+        };
+        Ok(config)
+    }
+}
+
+fn tls_error(err: impl std::fmt::Display) -> SplunkError {
+    SplunkError::Generic(format!("Failed to set up TLS: {err}"))
+}
+
+/// Runs the usual certificate validation (unless it's turned off), then checks the server's certificate against the
+/// pins
+#[derive(Debug)]
+struct PinnedVerifier {
+    inner: Option<Arc<dyn ServerCertVerifier>>,
+    pins: Vec<Pin>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl PinnedVerifier {
+    /// Only the end-entity certificate counts - matching a CA would let any server that sends the pinned CA's
+    /// (public) certificate along through
+    fn matches(&self, end_entity: &CertificateDer<'_>) -> bool {
+        let certificate = Pin::Certificate(sha256(end_entity));
+        let spki = webpki::EndEntityCert::try_from(end_entity)
+            .ok()
+            .map(|cert| Pin::Spki(sha256(&cert.subject_public_key_info())));
+        self.pins
+            .iter()
+            .any(|pin| *pin == certificate || Some(*pin) == spki)
+    }
+}
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hash = [0; 32];
+    hash.copy_from_slice(digest(&SHA256, data).as_ref());
+    hash
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Some(inner) = &self.inner {
+            inner.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        }
+        match self.matches(end_entity) {
+            true => Ok(ServerCertVerified::assertion()),
+            false => {
+                debug!("The certificate for {server_name:?} doesn't match any of the pins");
+                Err(rustls::Error::InvalidCertificate(
+                    CertificateError::ApplicationVerificationFailure,
+                ))
+            }
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        match &self.inner {
+            Some(inner) => inner.verify_tls12_signature(message, cert, dss),
+            None => rustls::crypto::verify_tls12_signature(
+                message,
+                cert,
+                dss,
+                &self.provider.signature_verification_algorithms,
+            ),
+        }
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        match &self.inner {
+            Some(inner) => inner.verify_tls13_signature(message, cert, dss),
+            None => rustls::crypto::verify_tls13_signature(
+                message,
+                cert,
+                dss,
+                &self.provider.signature_verification_algorithms,
+            ),
+        }
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        match &self.inner {
+            Some(inner) => inner.supported_verify_schemes(),
+            None => self
+                .provider
+                .signature_verification_algorithms
+                .supported_schemes(),
+        }
+    }
+}
+
+/// Looks up the real host in place of the server name, when each connection's made so DNS changes are picked up.
+/// Every other name is looked up as normal.
+struct ServerNameResolver {
+    server_name: String,
+    hostname: String,
+}
+
+impl Resolve for ServerNameResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = match name.as_str().eq_ignore_ascii_case(&self.server_name) {
+            true => self.hostname.clone(),
+            false => name.as_str().to_string(),
+        };
+        Box::pin(async move {
+            // the port comes from the URL
+            let addresses: Vec<SocketAddr> =
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            debug!("Resolved {} to {:?}", host, addresses);
+            let addresses: Addrs = Box::new(addresses.into_iter());
+            Ok(addresses)
+        })
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, SplunkError> {
+    std::fs::read(path)
+        .map_err(|err| SplunkError::Generic(format!("Failed to read {}: {}", path.display(), err)))
+}